@@ -1,5 +1,3 @@
-use std::collections::HashSet;
-
 use anyhow::Context;
 use itertools::Itertools;
 
@@ -21,6 +19,17 @@ impl Direction {
             West => East,
         }
     }
+
+    //the neighbouring position in this direction, if it isn't off the top or left
+    fn step(&self, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        use Direction::*;
+        Some(match self {
+            North => (x, y.checked_sub(1)?),
+            East => (x + 1, y),
+            South => (x, y + 1),
+            West => (x.checked_sub(1)?, y),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,6 +56,13 @@ impl Pipe {
         }
     }
 
+    fn from_directions(a: Direction, b: Direction) -> Option<Self> {
+        use Pipe::*;
+        [NE, NS, NW, ES, EW, SW]
+            .into_iter()
+            .find(|p| p.has_access(a) && p.has_access(b) && a != b)
+    }
+
     fn free_directions(&self) -> [Direction; 2] {
         use Direction::*;
         use Pipe::*;
@@ -60,19 +76,79 @@ impl Pipe {
         }
     }
 
+    fn box_char(self) -> char {
+        use Pipe::*;
+        match self {
+            NE => '└',
+            NS => '│',
+            NW => '┘',
+            ES => '┌',
+            EW => '─',
+            SW => '┐',
+        }
+    }
+
     fn has_access(&self, d: Direction) -> bool {
         self.free_directions().contains(&d)
     }
 
-    fn next_direction(&self, d: Direction) -> Direction {
+    //the way out of this pipe when entering it heading `d`, if it can be entered that way
+    fn next_direction(&self, d: Direction) -> Option<Direction> {
         let [a, b] = self.free_directions();
         match d.opposite() {
-            x if x == a => b,
-            x if x == b => a,
-            _ => panic!("incorrect approach"),
+            x if x == a => Some(b),
+            x if x == b => Some(a),
+            _ => None,
         }
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MazeError {
+    NoStart,
+    UnevenRows {
+        line: usize,
+    },
+    //the start needs exactly two neighbours leading into it
+    StartConnections {
+        connections: usize,
+    },
+    //the pipe at `at` doesn't connect back to the one before it
+    BrokenLoop {
+        at: (usize, usize),
+    },
+    LeavesGrid {
+        from: (usize, usize),
+        heading: Direction,
+    },
+}
+
+impl std::fmt::Display for MazeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use MazeError::*;
+        match self {
+            NoStart => write!(f, "no start tile"),
+            UnevenRows { line } => write!(f, "line {line} is a different width to the first"),
+            StartConnections { connections: 0 } => write!(f, "nothing connects to the start"),
+            StartConnections { connections: 1 } => write!(f, "the start is a dead end"),
+            StartConnections { connections } => write!(
+                f,
+                "the start is ambiguous, {connections} pipes connect to it"
+            ),
+            BrokenLoop { at: (x, y) } => write!(f, "the loop is broken at ({x}, {y})"),
+            LeavesGrid {
+                from: (x, y),
+                heading,
+            } => write!(
+                f,
+                "the loop leaves the grid heading {heading:?} from ({x}, {y})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MazeError {}
+
 struct Grid {
     inner: Vec<Option<Pipe>>,
     width: usize,
@@ -81,31 +157,50 @@ struct Grid {
 }
 
 impl Grid {
-    fn from_str(input: &str) -> Option<Self> {
+    //the start tile takes the shape of the only pipe which connects to its two neighbours
+    fn from_str(input: &str) -> Result<Self, MazeError> {
         let lines: Vec<_> = input.lines().collect();
-        let width = lines.first()?.len();
+        let width = lines.first().map_or(0, |line| line.chars().count());
         let height = lines.len();
-        let start = if let (start_y, Some(start_x)) = lines
+        if let Some(line) = lines.iter().position(|line| line.chars().count() != width) {
+            return Err(MazeError::UnevenRows { line: line + 1 });
+        }
+        let (start_y, start_x) = lines
             .iter()
-            .map(|line| line.chars().position(|c| c == 'S'))
-            .find_position(Option::is_some)?
-        {
-            (start_x, start_y)
-        } else {
-            unreachable!()
-        };
+            .enumerate()
+            .find_map(|(y, line)| Some((y, line.chars().position(|c| c == 'S')?)))
+            .ok_or(MazeError::NoStart)?;
 
         let inner = lines
             .into_iter()
             .flat_map(|line| line.chars().map(Pipe::from_char))
             .collect();
 
-        Some(Self {
+        let mut grid = Self {
             inner,
             width,
             height,
-            start,
-        })
+            start: (start_x, start_y),
+        };
+
+        use Direction::*;
+        let connections = [North, East, South, West]
+            .into_iter()
+            .filter(|d| {
+                d.step(grid.start)
+                    .and_then(|p| grid.get(p))
+                    .is_some_and(|pipe| pipe.has_access(d.opposite()))
+            })
+            .collect_vec();
+        let shape = match connections[..] {
+            [a, b] => Pipe::from_directions(a, b),
+            _ => None,
+        };
+        grid.inner[width * start_y + start_x] = Some(shape.ok_or(MazeError::StartConnections {
+            connections: connections.len(),
+        })?);
+
+        Ok(grid)
     }
 
     fn get(&self, (x, y): (usize, usize)) -> Option<Pipe> {
@@ -115,95 +210,402 @@ impl Grid {
 
         self.inner[self.width * y + x]
     }
-}
 
-struct Traveller<'a> {
-    position: (usize, usize),
-    heading: Direction,
-    grid: &'a Grid,
-}
+    //follows the pipe at `from` out along `heading` until it comes back round, returning
+    //every tile of the loop in order, beginning with `from`
+    fn trace(
+        &self,
+        from: (usize, usize),
+        heading: Direction,
+    ) -> Result<Vec<(usize, usize)>, MazeError> {
+        let mut tiles = Vec::new();
+        self.walk(from, heading, &mut tiles)?;
+        Ok(tiles)
+    }
 
-impl<'a> Traveller<'a> {
-    fn new(grid: &'a Grid) -> Self {
-        use Direction::*;
-        let position @ (x, y) = grid.start;
-
-        for (x, y, heading) in [
-            (x, y.saturating_sub(1), North),
-            (x, y + 1, South),
-            (x + 1, y, East),
-            (x.saturating_sub(1), y, West),
-        ] {
-            if let Some(pipe) = grid.get((x, y)) {
-                if pipe.has_access(heading.opposite()) {
-                    // println!("{:?} {:?}", position, heading);
-                    return Self {
-                        position,
-                        heading,
-                        grid,
-                    };
-                }
+    //pushes each tile of the walk to `tiles` as it goes, so a walk which breaks off leaves
+    //behind the tiles it got through
+    fn walk(
+        &self,
+        from: (usize, usize),
+        mut heading: Direction,
+        tiles: &mut Vec<(usize, usize)>,
+    ) -> Result<(), MazeError> {
+        tiles.push(from);
+        let mut position = from;
+        loop {
+            //pipes only ever connect in pairs, so the walk can't loop without returning
+            position = heading
+                .step(position)
+                .filter(|&(x, y)| x < self.width && y < self.height)
+                .ok_or(MazeError::LeavesGrid {
+                    from: position,
+                    heading,
+                })?;
+            heading = self
+                .get(position)
+                .and_then(|pipe| pipe.next_direction(heading))
+                .ok_or(MazeError::BrokenLoop { at: position })?;
+            //only closed if `from` connects back the way the walk came in
+            if position == from {
+                return Ok(());
             }
+            tiles.push(position);
         }
-
-        panic!("Invalid grid")
     }
-}
 
-impl<'a> Iterator for Traveller<'a> {
-    type Item = (usize, usize);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let (x, y) = self.position;
-        use Direction::*;
-        self.position = match self.heading {
-            North => (x, y - 1),
-            East => (x + 1, y),
-            South => (x, y + 1),
-            West => (x - 1, y),
-        };
+    //every tile of the loop through the start, in order, beginning with the start
+    fn main_loop(&self) -> Result<Vec<(usize, usize)>, MazeError> {
+        let heading = self
+            .get(self.start)
+            .expect("start tile shape is inferred when parsing")
+            .free_directions()[0];
+        self.trace(self.start, heading)
+    }
 
-        self.heading = self.grid.get(self.position)?.next_direction(self.heading);
-        // println!("{:?} {:?}", self.position, self.heading);
+    //every closed loop of pipes in the grid, each beginning with its topmost, leftmost tile
+    fn loops(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = vec![false; self.inner.len()];
+        let mut loops = Vec::new();
+        for (i, pipe) in self.inner.iter().enumerate() {
+            let Some(pipe) = pipe else { continue };
+            if seen[i] {
+                continue;
+            }
+            seen[i] = true;
+            let from = (i % self.width, i / self.width);
+            //if either way out of a pipe doesn't lead back round, it's on no loop at all, and
+            //nor is any pipe the walk went through, so none of them is walked from again
+            let mut tiles = Vec::new();
+            let closed = self.walk(from, pipe.free_directions()[0], &mut tiles);
+            for &(x, y) in &tiles {
+                seen[self.width * y + x] = true;
+            }
+            if closed.is_ok() {
+                loops.push(tiles);
+            }
+        }
+        loops
+    }
 
-        Some(self.position)
+    fn loop_mask(&self, main_loop: &[(usize, usize)]) -> Vec<bool> {
+        let mut on_loop = vec![false; self.inner.len()];
+        for &(x, y) in main_loop {
+            on_loop[self.width * y + x] = true;
+        }
+        on_loop
     }
-}
 
-fn main() -> anyhow::Result<()> {
-    let input = std::fs::read_to_string("inputs/day10.txt")?;
-    let grid = Grid::from_str(&input).context("Invalid grid")?;
+    //scans each row, flipping between outside and inside at every loop pipe with a northern
+    //connection, so that a run along the loop only counts if it crosses over
+    fn enclosed_mask(&self, main_loop: &[(usize, usize)]) -> Vec<bool> {
+        let on_loop = self.loop_mask(main_loop);
 
-    let traveller = Traveller::new(&grid);
-    let mut l = HashSet::new();
+        let mut enclosed = vec![false; self.inner.len()];
+        for y in 0..self.height {
+            let mut inside = false;
+            for x in 0..self.width {
+                let i = self.width * y + x;
+                if on_loop[i] {
+                    if self
+                        .get((x, y))
+                        .is_some_and(|p| p.has_access(Direction::North))
+                    {
+                        inside = !inside;
+                    }
+                } else {
+                    enclosed[i] = inside;
+                }
+            }
+        }
+        enclosed
+    }
 
-    l.insert(traveller.position);
-    l.extend(traveller);
+    fn enclosed_by_ray_casting(&self, main_loop: &[(usize, usize)]) -> usize {
+        self.enclosed_mask(main_loop)
+            .into_iter()
+            .filter(|&inside| inside)
+            .count()
+    }
 
-    println!("10.1: {}", (l.len()) / 2);
+    //draws the main loop in box-drawing characters. With colour, junk pipes are dimmed and
+    //enclosed tiles shaded; without, every tile off the loop is marked `I`nside or `O`utside
+    fn render(&self, main_loop: &[(usize, usize)], colour: bool) -> String {
+        const RESET: &str = "\x1b[0m";
+        const LOOP: &str = "\x1b[1;33m";
+        const START: &str = "\x1b[1;31m";
+        const JUNK: &str = "\x1b[2m";
+        const INSIDE: &str = "\x1b[42m";
 
-    let mut inside = 0;
+        let on_loop = self.loop_mask(main_loop);
+        let enclosed = self.enclosed_mask(main_loop);
 
-    for y in 0..grid.height {
-        let mut north = 0;
-        let mut south = 0;
-        for x in 0..grid.width {
-            if l.contains(&(x, y)) {
-                if let Some(p) = grid.get((x, y)) {
-                    if p == Pipe::NE || p == Pipe::NS || p == Pipe::NW {
-                        north += 1;
+        let mut out = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = self.width * y + x;
+                let shape = self.get((x, y)).map(Pipe::box_char);
+                match (colour, on_loop[i], shape) {
+                    (false, true, Some(c)) => out.push(c),
+                    (false, _, _) => out.push(if enclosed[i] { 'I' } else { 'O' }),
+                    (true, true, Some(c)) => {
+                        let style = if (x, y) == self.start { START } else { LOOP };
+                        out.extend([style, c.encode_utf8(&mut [0; 4]), RESET]);
                     }
-                    if p == Pipe::ES || p == Pipe::NS || p == Pipe::SW {
-                        south += 1;
+                    (true, _, shape) => {
+                        let c = shape.unwrap_or('·');
+                        let style = if enclosed[i] { INSIDE } else { "" };
+                        out.extend([style, JUNK, c.encode_utf8(&mut [0; 4]), RESET]);
                     }
                 }
-            } else if north % 2 == 1 && south % 2 == 1 {
-                inside += 1;
             }
+            out.push('\n');
         }
+        out
     }
+}
+
+//the shoelace formula gives the loop's area, and Pick's theorem A = i + b/2 - 1 recovers
+//the number of interior points from it
+fn enclosed_by_picks(main_loop: &[(usize, usize)]) -> usize {
+    let twice_area = main_loop
+        .iter()
+        .circular_tuple_windows()
+        .map(|(&(x1, y1), &(x2, y2))| (x1 * y2) as i64 - (x2 * y1) as i64)
+        .sum::<i64>()
+        .unsigned_abs() as usize;
+    (twice_area + 2 - main_loop.len()) / 2
+}
+
+fn main() -> anyhow::Result<()> {
+    let input = std::fs::read_to_string("inputs/day10.txt")?;
+    let grid = Grid::from_str(&input)?;
+
+    let main_loop = grid.main_loop()?;
 
-    println!("10.2: {}", inside);
+    println!("10.1: {}", main_loop.len() / 2);
+    //both methods give the same count, `--ray-casting` picks the scanning one
+    let enclosed = if std::env::args().any(|arg| arg == "--ray-casting") {
+        grid.enclosed_by_ray_casting(&main_loop)
+    } else {
+        enclosed_by_picks(&main_loop)
+    };
+    println!("10.2: {}", enclosed);
+
+    //`--render` draws the maze to the terminal, `--render-to FILE` as plain text to a file,
+    //and `--loops` lists every closed loop in the grid
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--render" => {
+                use std::io::IsTerminal;
+                print!(
+                    "{}",
+                    grid.render(&main_loop, std::io::stdout().is_terminal())
+                );
+            }
+            "--render-to" => {
+                let path = args.next().context("--render-to needs a file")?;
+                std::fs::write(path, grid.render(&main_loop, false))?;
+            }
+            "--loops" => {
+                for tiles in grid.loops() {
+                    let (x, y) = tiles[0];
+                    println!("loop of {} tiles from ({x}, {y})", tiles.len());
+                }
+            }
+            _ => {}
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLES: [(&str, usize); 4] = [
+        (
+            "\
+..F7.
+.FJ|.
+SJ.L7
+|F--J
+LJ...",
+            1,
+        ),
+        (
+            "\
+...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........",
+            4,
+        ),
+        (
+            "\
+.F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...",
+            8,
+        ),
+        (
+            "\
+FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L",
+            10,
+        ),
+    ];
+
+    #[test]
+    fn test_start_shape() {
+        let shapes = EXAMPLES.map(|(input, _)| {
+            let grid = Grid::from_str(input).unwrap();
+            grid.get(grid.start).unwrap()
+        });
+        assert_eq!(shapes, [Pipe::ES, Pipe::ES, Pipe::ES, Pipe::SW]);
+    }
+
+    #[test]
+    fn test_main_loop() {
+        let grid = Grid::from_str(EXAMPLES[0].0).unwrap();
+        let main_loop = grid.main_loop().unwrap();
+        assert_eq!(main_loop.len() / 2, 8);
+        assert_eq!(main_loop[0], (0, 2));
+        assert!(main_loop.iter().all_unique());
+        //consecutive tiles, including last to first, are adjacent
+        assert!(main_loop
+            .iter()
+            .circular_tuple_windows()
+            .all(|(a, b)| a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1));
+    }
+
+    #[test]
+    fn test_enclosed_methods_agree() {
+        for (input, enclosed) in EXAMPLES {
+            let grid = Grid::from_str(input).unwrap();
+            let main_loop = grid.main_loop().unwrap();
+            assert_eq!(grid.enclosed_by_ray_casting(&main_loop), enclosed);
+            assert_eq!(enclosed_by_picks(&main_loop), enclosed);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let grid = Grid::from_str(EXAMPLES[1].0).unwrap();
+        let main_loop = grid.main_loop().unwrap();
+        assert_eq!(
+            grid.render(&main_loop, false),
+            "\
+OOOOOOOOOOO
+O┌───────┐O
+O│┌─────┐│O
+O││OOOOO││O
+O││OOOOO││O
+O│└─┐O┌─┘│O
+O│II│O│II│O
+O└──┘O└──┘O
+OOOOOOOOOOO
+"
+        );
+
+        let coloured = grid.render(&main_loop, true);
+        assert_eq!(coloured.matches("\x1b[42m").count(), 4);
+        assert!(coloured.contains("\x1b[1;31m┌\x1b[0m"));
+        //stripping the escapes leaves the same number of tiles
+        let plain: String = coloured
+            .split("\x1b[")
+            .map(|s| s.trim_start_matches(|c: char| c.is_ascii_digit() || c == ';' || c == 'm'))
+            .collect();
+        assert_eq!(plain.chars().filter(|&c| c != '\n').count(), 11 * 9);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |input| {
+            Grid::from_str(input)
+                .and_then(|g| g.main_loop())
+                .unwrap_err()
+        };
+        assert_eq!(err("..\n.."), MazeError::NoStart);
+        assert_eq!(err("S-7\n|.|\nL-"), MazeError::UnevenRows { line: 3 });
+        assert_eq!(
+            err("...\n.S.\n..."),
+            MazeError::StartConnections { connections: 0 }
+        );
+        assert_eq!(
+            err(".|.\n.S.\n..."),
+            MazeError::StartConnections { connections: 1 }
+        );
+        assert_eq!(
+            err(".|.\n-S-\n.|."),
+            MazeError::StartConnections { connections: 4 }
+        );
+        assert_eq!(err("S-7\n|.|\nL-."), MazeError::BrokenLoop { at: (2, 2) });
+        assert_eq!(err("S-7\n|.|\nL-7"), MazeError::BrokenLoop { at: (2, 2) });
+        assert_eq!(
+            err("S--\n|..\nL--"),
+            MazeError::LeavesGrid {
+                from: (2, 0),
+                heading: Direction::East
+            }
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        let grid = Grid::from_str(
+            "\
+S-7F7.
+|.|||.
+L-JLJ7
+F7.-.|
+LJ..-J",
+        )
+        .unwrap();
+        let loops = grid.loops();
+        assert_eq!(loops.len(), 3);
+        assert_eq!(
+            loops.iter().map(|l| (l[0], l.len())).collect_vec(),
+            [((0, 0), 8), ((3, 0), 6), ((0, 3), 4)]
+        );
+        assert_eq!(loops[0], grid.main_loop().unwrap());
+
+        let grid = Grid::from_str(EXAMPLES[3].0).unwrap();
+        let main_loop = grid.main_loop().unwrap();
+        assert!(grid
+            .loops()
+            .iter()
+            .any(|l| l.len() == main_loop.len() && l.iter().all(|p| main_loop.contains(p))));
+
+        //the walk north from the bottom right L comes back round to it from the west, where it
+        //has no opening
+        let grid = Grid::from_str("S-7F7\nL-JLL").unwrap();
+        assert_eq!(grid.loops(), [grid.main_loop().unwrap()]);
+        assert!(matches!(
+            grid.trace((4, 1), Direction::North),
+            Err(MazeError::BrokenLoop { at: (4, 1) })
+        ));
+    }
+}
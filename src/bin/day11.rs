@@ -1,58 +1,475 @@
-use std::collections::BTreeSet;
+use itertools::{Either, Itertools};
 
-use itertools::Itertools;
+//total distance between every pair of galaxies, as a linear function of the expansion factors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DistanceFormula {
+    //pairwise distance along each axis before expansion
+    base: (u128, u128),
+    //empty columns and rows crossed, summed over every pair
+    gaps: (u128, u128),
+}
+
+impl DistanceFormula {
+    fn at(&self, expansion_x: u64, expansion_y: u64) -> u128 {
+        self.base.0
+            + self.base.1
+            + (expansion_x as u128 - 1) * self.gaps.0
+            + (expansion_y as u128 - 1) * self.gaps.1
+    }
+}
+
+#[derive(Debug, Clone)]
+struct GalaxyMap {
+    //in reading order, so the puzzle's galaxy n is at index n - 1
+    galaxies: Vec<(usize, usize)>,
+    //number of empty columns to the left of each column, and rows above each row
+    empty_columns_before: Vec<usize>,
+    empty_rows_before: Vec<usize>,
+}
+
+//sum of |a - b| over every pair, for values which are already sorted
+fn sorted_pairwise_sum(values: impl IntoIterator<Item = usize>) -> u128 {
+    let mut prefix = 0;
+    let mut total = 0;
+    for (i, v) in values.into_iter().enumerate() {
+        total += v as u128 * i as u128 - prefix;
+        prefix += v as u128;
+    }
+    total
+}
+
+//running count of empty lines before each index
+fn empty_before(occupied: &[bool]) -> Vec<usize> {
+    occupied
+        .iter()
+        .scan(0, |empty, &occupied| {
+            let before = *empty;
+            *empty += !occupied as usize;
+            Some(before)
+        })
+        .collect()
+}
+
+impl GalaxyMap {
+    fn from_str(input: &str) -> Self {
+        let width = input.lines().next().map_or(0, str::len);
+        let height = input.lines().count();
+
+        let galaxies = input
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| line.bytes().positions(|c| c == b'#').map(move |x| (x, y)))
+            .collect_vec();
+
+        let mut occupied_columns = vec![false; width];
+        let mut occupied_rows = vec![false; height];
+        for &(x, y) in &galaxies {
+            occupied_columns[x] = true;
+            occupied_rows[y] = true;
+        }
+
+        Self {
+            galaxies,
+            empty_columns_before: empty_before(&occupied_columns),
+            empty_rows_before: empty_before(&occupied_rows),
+        }
+    }
+
+    //expansion only ever pushes galaxies further apart, so sorting the unexpanded
+    //coordinates once also sorts them for every expansion factor
+    fn formula(&self) -> DistanceFormula {
+        let xs = self
+            .galaxies
+            .iter()
+            .map(|g| g.0)
+            .sorted_unstable()
+            .collect_vec();
+        let ys = self
+            .galaxies
+            .iter()
+            .map(|g| g.1)
+            .sorted_unstable()
+            .collect_vec();
+        DistanceFormula {
+            base: (
+                sorted_pairwise_sum(xs.iter().copied()),
+                sorted_pairwise_sum(ys.iter().copied()),
+            ),
+            gaps: (
+                sorted_pairwise_sum(xs.iter().map(|&x| self.empty_columns_before[x])),
+                sorted_pairwise_sum(ys.iter().map(|&y| self.empty_rows_before[y])),
+            ),
+        }
+    }
+
+    fn sum_pairwise_distances(&self, expansion_x: u64, expansion_y: u64) -> u128 {
+        self.formula().at(expansion_x, expansion_y)
+    }
+
+    fn expanded(&self, i: usize, expansion_x: u64, expansion_y: u64) -> (u64, u64) {
+        let (x, y) = self.galaxies[i];
+        (
+            x as u64 + (expansion_x - 1) * self.empty_columns_before[x] as u64,
+            y as u64 + (expansion_y - 1) * self.empty_rows_before[y] as u64,
+        )
+    }
+
+    fn distance(&self, a: usize, b: usize, expansion_x: u64, expansion_y: u64) -> u64 {
+        let (ax, ay) = self.expanded(a, expansion_x, expansion_y);
+        let (bx, by) = self.expanded(b, expansion_x, expansion_y);
+        ax.abs_diff(bx) + ay.abs_diff(by)
+    }
+
+    //the most distant pair and their distance. Manhattan distance is the larger of the spreads
+    //along the two diagonals, so only the extremes of x + y and x - y matter
+    fn farthest_pair(&self, expansion_x: u64, expansion_y: u64) -> Option<(usize, usize, u64)> {
+        if self.galaxies.len() < 2 {
+            return None;
+        }
+        let diagonal = |f: fn(i128, i128) -> i128| {
+            let (min, max) = (0..self.galaxies.len())
+                .minmax_by_key(|&i| {
+                    let (x, y) = self.expanded(i, expansion_x, expansion_y);
+                    f(x as i128, y as i128)
+                })
+                .into_option()
+                .expect("there are at least two galaxies");
+            (min, max, self.distance(min, max, expansion_x, expansion_y))
+        };
+        [diagonal(|x, y| x + y), diagonal(|x, y| x - y)]
+            .into_iter()
+            .max_by_key(|&(_, _, d)| d)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    Manhattan,
+    //king moves
+    Chebyshev,
+    Euclidean,
+}
+
+impl Metric {
+    fn distance(self, (ax, ay): (i64, i64), (bx, by): (i64, i64)) -> f64 {
+        let (dx, dy) = (ax.abs_diff(bx), ay.abs_diff(by));
+        match self {
+            Metric::Manhattan => (dx + dy) as f64,
+            Metric::Chebyshev => dx.max(dy) as f64,
+            Metric::Euclidean => (dx as f64).hypot(dy as f64),
+        }
+    }
+
+    //coordinates in which this metric becomes the Chebyshev distance, if it's integral.
+    //Rotating by 45° turns Manhattan distance into Chebyshev distance
+    fn chebyshev_coordinates(self, (x, y): (i64, i64)) -> Option<(i64, i64)> {
+        match self {
+            Metric::Manhattan => Some((x + y, x - y)),
+            Metric::Chebyshev => Some((x, y)),
+            Metric::Euclidean => None,
+        }
+    }
+}
+
+//counts of values, queried by prefix
+struct Fenwick(Vec<u64>);
+
+impl Fenwick {
+    fn add(&mut self, mut i: usize, delta: i64) {
+        i += 1;
+        while i <= self.0.len() {
+            self.0[i - 1] = self.0[i - 1].wrapping_add_signed(delta);
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    //number of values at indices below i
+    fn below(&self, mut i: usize) -> u64 {
+        let mut total = 0;
+        while i > 0 {
+            total += self.0[i - 1];
+            i -= i & i.wrapping_neg();
+        }
+        total
+    }
+}
+
+//pairs of points no further than `d` apart along either axis, sweeping a window of width `d`
+//along the first axis with the second axis held in a Fenwick tree
+fn pairs_within(points: &[(i64, i64)], d: i64) -> u64 {
+    let points = points.iter().copied().sorted_unstable().collect_vec();
+    let vs = points
+        .iter()
+        .map(|p| p.1)
+        .sorted_unstable()
+        .dedup()
+        .collect_vec();
+    let rank = |v: i64| vs.partition_point(|&w| w < v);
+
+    let mut window = Fenwick(vec![0; vs.len()]);
+    let mut oldest = 0;
+    let mut pairs = 0;
+    for &(u, v) in &points {
+        while points[oldest].0 < u - d {
+            window.add(rank(points[oldest].1), -1);
+            oldest += 1;
+        }
+        pairs += window.below(rank(v + d + 1)) - window.below(rank(v - d));
+        window.add(rank(v), 1);
+    }
+    pairs
+}
+
+impl GalaxyMap {
+    fn expanded_all(&self, expansion_x: u64, expansion_y: u64) -> Vec<(i64, i64)> {
+        (0..self.galaxies.len())
+            .map(|i| {
+                let (x, y) = self.expanded(i, expansion_x, expansion_y);
+                (x as i64, y as i64)
+            })
+            .collect()
+    }
+
+    //O(n log n) for Manhattan and Chebyshev distance, but every pair for Euclidean
+    fn sum_distances(&self, metric: Metric, expansion_x: u64, expansion_y: u64) -> f64 {
+        let points = self.expanded_all(expansion_x, expansion_y);
+        match metric {
+            Metric::Manhattan => self.sum_pairwise_distances(expansion_x, expansion_y) as f64,
+            //max(|dx|, |dy|) is half of |dx + dy| + |dx - dy|
+            Metric::Chebyshev => {
+                let axis = |f: fn((i64, i64)) -> i64| {
+                    let values = points.iter().map(|&p| f(p)).sorted_unstable().collect_vec();
+                    let min = values.first().copied().unwrap_or(0);
+                    sorted_pairwise_sum(values.into_iter().map(|v| (v - min) as usize))
+                };
+                ((axis(|(x, y)| x + y) + axis(|(x, y)| x - y)) / 2) as f64
+            }
+            Metric::Euclidean => points
+                .iter()
+                .tuple_combinations()
+                .map(|(&a, &b)| metric.distance(a, b))
+                .sum(),
+        }
+    }
+
+    //each galaxy's closest neighbour, as (galaxy, neighbour, distance). Every metric is at least
+    //the distance along x, so the search outwards in x order stops once that exceeds the best
+    fn nearest_neighbours(
+        &self,
+        metric: Metric,
+        expansion_x: u64,
+        expansion_y: u64,
+    ) -> Vec<(usize, usize, f64)> {
+        let points = self.expanded_all(expansion_x, expansion_y);
+        let by_x = (0..points.len())
+            .sorted_unstable_by_key(|&i| points[i])
+            .collect_vec();
+
+        let mut nearest = vec![None; points.len()];
+        for (p, &i) in by_x.iter().enumerate() {
+            let mut best: Option<(usize, f64)> = None;
+            let left = Either::Left(by_x[..p].iter().rev());
+            let right = Either::Right(by_x[p + 1..].iter());
+            for side in [left, right] {
+                for &j in side {
+                    let dx = points[i].0.abs_diff(points[j].0) as f64;
+                    if best.is_some_and(|(_, d)| dx > d) {
+                        break;
+                    }
+                    let d = metric.distance(points[i], points[j]);
+                    if best.is_none_or(|(_, b)| d < b) {
+                        best = Some((j, d));
+                    }
+                }
+            }
+            nearest[i] = best.map(|(j, d)| (i, j, d));
+        }
+        nearest.into_iter().flatten().collect()
+    }
+
+    //the lower median over every pair. For Manhattan and Chebyshev distance this binary
+    //searches on the distance, counting pairs within it in O(n log n)
+    fn median_distance(&self, metric: Metric, expansion_x: u64, expansion_y: u64) -> Option<f64> {
+        let points = self.expanded_all(expansion_x, expansion_y);
+        let n_pairs = points.len() * points.len().saturating_sub(1) / 2;
+        if n_pairs == 0 {
+            return None;
+        }
+        let k = n_pairs.div_ceil(2);
+
+        if let Some(rotated) = points
+            .iter()
+            .map(|&p| metric.chebyshev_coordinates(p))
+            .collect::<Option<Vec<_>>>()
+        {
+            let (mut lo, mut hi) = (0, {
+                let (min, max) = rotated
+                    .iter()
+                    .flat_map(|&(u, v)| [u, v])
+                    .minmax()
+                    .into_option()?;
+                max - min
+            });
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if pairs_within(&rotated, mid) >= k as u64 {
+                    hi = mid;
+                } else {
+                    lo = mid + 1;
+                }
+            }
+            return Some(lo as f64);
+        }
+
+        let mut distances = points
+            .iter()
+            .tuple_combinations()
+            .map(|(&a, &b)| metric.distance(a, b))
+            .collect_vec();
+        let (_, median, _) = distances.select_nth_unstable_by(k - 1, f64::total_cmp);
+        Some(*median)
+    }
+}
 
 fn main() -> anyhow::Result<()> {
     let input = std::fs::read_to_string("inputs/day11.txt")?;
+    let map = GalaxyMap::from_str(&input);
 
-    let width = input.lines().next().unwrap().len();
-    let height = input.lines().count();
+    println!("11.1: {}", map.sum_pairwise_distances(2, 2));
+    println!("11.2: {}", map.sum_pairwise_distances(1_000_000, 1_000_000));
 
-    let mut galaxies = BTreeSet::new();
-    let mut gcount = 0;
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.bytes().enumerate() {
-            if c == b'#' {
-                galaxies.insert((x, y));
-                gcount += 1;
+    //`day11 EXPANSION_X [EXPANSION_Y]` looks at any other expansion, and `--metrics`
+    //compares distance metrics under it
+    let (metrics, args): (Vec<_>, Vec<_>) =
+        std::env::args().skip(1).partition(|arg| arg == "--metrics");
+    let mut args = args.into_iter();
+    if let Some(expansion_x) = args.next() {
+        let expansion_x: u64 = expansion_x.parse()?;
+        let expansion_y: u64 = args.next().map_or(Ok(expansion_x), |y| y.parse())?;
+        if expansion_x == 0 || expansion_y == 0 {
+            anyhow::bail!("expansion factors start at 1");
+        }
+
+        let formula = map.formula();
+        println!(
+            "total = {} + {} + {}(x - 1) + {}(y - 1)",
+            formula.base.0, formula.base.1, formula.gaps.0, formula.gaps.1
+        );
+        println!(
+            "{expansion_x} x {expansion_y}: {}",
+            formula.at(expansion_x, expansion_y)
+        );
+        if let Some((a, b, d)) = map.farthest_pair(expansion_x, expansion_y) {
+            println!("farthest: galaxies {} and {}, {d} apart", a + 1, b + 1);
+        }
+
+        if !metrics.is_empty() {
+            for metric in [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean] {
+                let nearest = map.nearest_neighbours(metric, expansion_x, expansion_y);
+                let closest = nearest.iter().min_by(|a, b| a.2.total_cmp(&b.2));
+                println!(
+                    "{metric:?}: total {}, median {:?}, closest pair {:?}",
+                    map.sum_distances(metric, expansion_x, expansion_y),
+                    map.median_distance(metric, expansion_x, expansion_y),
+                    closest.map(|&(a, b, d)| (a + 1, b + 1, d)),
+                );
             }
         }
     }
 
-    let empty_column_contributions: usize = (0..width)
-        .filter(|x| !galaxies.iter().any(|(gx, _)| gx == x))
-        .map(|x| {
-            //since BTreeSet is sorted, galaxies will be in order so we look for the first galaxy
-            let left = galaxies.iter().position(|(gx, _)| *gx >= x).unwrap();
-            left * (gcount - left)
-        })
-        .sum();
-
-    let empty_row_contributions: usize = (0..height)
-        .filter(|y| !galaxies.iter().any(|(_, gy)| gy == y))
-        .map(|y| {
-            //BTreeSet is not sorted for y accesses
-            let above = galaxies.iter().filter(|(_, gy)| *gy < y).count();
-            above * (gcount - above)
-        })
-        .sum();
+    Ok(())
+}
 
-    let normal_distance: usize = galaxies
-        .iter()
-        .combinations(2)
-        .map(|v| v[1].0.abs_diff(v[0].0) + v[1].1.abs_diff(v[0].1))
-        .sum();
-
-    let expansion_factor = empty_row_contributions + empty_column_contributions;
-    // println!("{}", d((1, 5), (4, 9), &empty_rows, &empty_columns));
-    println!(
-        "11.1: {}",
-        normal_distance + empty_column_contributions + empty_row_contributions
-    );
-    println!(
-        "11.2: {}",
-        normal_distance + expansion_factor * (1_000_000 - 1)
-    );
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    Ok(())
+    const EXAMPLE: &str = "\
+...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
+
+    #[test]
+    fn test_sum_pairwise_distances() {
+        let map = GalaxyMap::from_str(EXAMPLE);
+        assert_eq!(map.sum_pairwise_distances(2, 2), 374);
+        assert_eq!(map.sum_pairwise_distances(10, 10), 1030);
+        assert_eq!(map.sum_pairwise_distances(100, 100), 8410);
+
+        //against every pair, with different expansion along each axis
+        for (ex, ey) in [(1, 1), (2, 7), (1_000_000, 3)] {
+            let brute: u64 = (0..map.galaxies.len())
+                .tuple_combinations()
+                .map(|(a, b)| map.distance(a, b, ex, ey))
+                .sum();
+            assert_eq!(map.sum_pairwise_distances(ex, ey), brute as u128);
+        }
+    }
+
+    #[test]
+    fn test_pairs() {
+        let map = GalaxyMap::from_str(EXAMPLE);
+        assert_eq!(map.distance(4, 8, 2, 2), 9);
+        assert_eq!(map.distance(0, 6, 2, 2), 15);
+        assert_eq!(map.distance(2, 5, 2, 2), 17);
+        assert_eq!(map.distance(7, 8, 2, 2), 5);
+
+        for (ex, ey) in [(2, 2), (1, 50), (50, 1)] {
+            let brute = (0..map.galaxies.len())
+                .tuple_combinations()
+                .map(|(a, b)| map.distance(a, b, ex, ey))
+                .max();
+            let (a, b, d) = map.farthest_pair(ex, ey).unwrap();
+            assert_eq!(Some(d), brute);
+            assert_eq!(map.distance(a, b, ex, ey), d);
+        }
+        assert_eq!(GalaxyMap::from_str("..#..").farthest_pair(2, 2), None);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let map = GalaxyMap::from_str(EXAMPLE);
+        for metric in [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean] {
+            for (ex, ey) in [(1, 1), (2, 2), (3, 10), (100, 1)] {
+                let points = map.expanded_all(ex, ey);
+                let mut distances = points
+                    .iter()
+                    .tuple_combinations()
+                    .map(|(&a, &b)| metric.distance(a, b))
+                    .collect_vec();
+
+                let sum = map.sum_distances(metric, ex, ey);
+                assert!(
+                    (sum - distances.iter().sum::<f64>()).abs() < 1e-6,
+                    "{metric:?}"
+                );
+
+                distances.sort_unstable_by(f64::total_cmp);
+                assert_eq!(
+                    map.median_distance(metric, ex, ey),
+                    Some(distances[distances.len().div_ceil(2) - 1]),
+                    "{metric:?} {ex} {ey}"
+                );
+
+                for (i, j, d) in map.nearest_neighbours(metric, ex, ey) {
+                    assert_ne!(i, j);
+                    assert_eq!(d, metric.distance(points[i], points[j]));
+                    let best = (0..points.len())
+                        .filter(|&k| k != i)
+                        .map(|k| metric.distance(points[i], points[k]))
+                        .min_by(f64::total_cmp);
+                    assert_eq!(Some(d), best);
+                }
+            }
+        }
+        assert_eq!(map.sum_distances(Metric::Manhattan, 2, 2), 374.);
+        assert_eq!(map.nearest_neighbours(Metric::Euclidean, 2, 2).len(), 9);
+    }
 }
@@ -1,134 +1,121 @@
+use anyhow::Context;
+use aoc_2023::spring_row::SpringRow;
 use itertools::Itertools;
+use num::{BigUint, Zero};
 use rayon::prelude::*;
 
-struct State<'a> {
-    pattern: &'a str,
-    description: &'a [usize],
-    cache: Vec<Option<u64>>,
-    width: usize,
-}
-impl<'a> State<'a> {
-    fn new(pattern: &'a str, description: &'a [usize]) -> Self {
-        Self {
-            pattern,
-            description,
-            cache: vec![None; pattern.len() * description.len()],
-            width: pattern.len(),
-        }
-    }
-    fn get_cache(&self, (x, y): (usize, usize)) -> Option<u64> {
-        *self.cache.get(self.width * y + x).unwrap_or(&None)
-    }
-    fn set_cache(&mut self, (x, y): (usize, usize), value: u64) -> Option<u64> {
-        self.cache
-            .get_mut(y * self.width + x)
-            .and_then(|c| c.replace(value))
-    }
-}
-
 fn n_matches(pattern: &str, description: &[usize]) -> u64 {
-    fn n_matches_from_index(state: &mut State<'_>, pi: usize, di: usize) -> u64 {
-        //an empty description matches anything without any '#'s
-        if state.description.len() <= di {
-            let result = !state.pattern[pi..].contains('#') as u64;
-            return result;
-        }
-
-        let description = &state.description[di..];
-        if state.pattern.len() <= pi {
-            return 0;
-        }
-        let pattern = &state.pattern[pi..];
-        //now pattern and description are slices of the original pattern and description
-
-        //early return if remaining pattern isn't long enough
-        if pattern.len() + 1 < description.iter().map(|x| x + 1).sum::<usize>() {
-            return 0;
-        }
-
-        //the first length of '#'s to find
-        let first = description.first().unwrap();
-
-        //index of the first thing that _could_ be a '#'
-        let index = pattern.find(['?', '#']);
-
-        //early return if no matches
-        if index.is_none() {
-            return 0;
-        }
-        let index = index.unwrap();
-
-        //early return with too short a pattern
-        if pattern.len() < *first + index {
-            return 0;
-        }
-
-        let mut result = 0;
-
-        // principal branch: recurse with '?' is '.'
-        if &pattern[index..=index] == "?" {
-            result += cached(state, pi + index + 1, di);
-        }
+    SpringRow::new(pattern, description).count()
+}
 
-        //try taking the next "first" characters, they should all be '#' or '?'
-        if !(pattern[index..index + first]
-            .chars()
-            .all(|c| ['?', '#'].contains(&c)))
-        {
-            return result;
-        };
+//the pattern repeated `n` times with unknowns between the copies, and the groups `n` times over
+fn unfold(pattern: &str, description: &[usize], n: usize) -> (String, Vec<usize>) {
+    (
+        std::iter::repeat_n(pattern, n).join("?"),
+        description.repeat(n),
+    )
+}
 
-        // the next character after first has matched
-        let next = pattern.get((index + first)..=(index + first));
+//the unfolded counts grow exponentially with `n`, so soon outgrow any fixed width
+fn n_unfolded_matches(pattern: &str, description: &[usize], n: usize) -> BigUint {
+    let (pattern, description) = unfold(pattern, description, n);
+    SpringRow::new(&pattern, &description).count()
+}
 
-        //if we're at the end of the string, we can return
-        if next.is_none() {
-            result += cached(state, pi + index + first, di + 1);
-            return result;
-        }
+fn parse_line(line: &str) -> anyhow::Result<(&str, Vec<usize>)> {
+    let (pattern, description) = line
+        .split_once(' ')
+        .context("line is a pattern and its groups")?;
+    let description = description
+        .split(',')
+        .filter(|g| !g.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<usize>, _>>()?;
+    Ok((pattern, description))
+}
 
-        let next = next.unwrap();
-        //if the sequence of '#'s is longer than we expected, we're done
-        if next == "#" {
-            return result;
-        }
+//the count, the cells every arrangement agrees on and the first few arrangements; unlike
+//the whole input, a single row is only unfolded when asked to be
+fn describe_row(line: &str, unfold_by: Option<usize>) -> anyhow::Result<String> {
+    let (pattern, groups) = parse_line(line)?;
+    let (pattern, groups) = unfold(pattern, &groups, unfold_by.unwrap_or(1));
+    let row = SpringRow::<BigUint>::new(&pattern, &groups);
 
-        //otherwise, we've found first '#' or '?'s, terminated by a '.' or '?', so we recurse
-        result + cached(state, pi + index + first + 1, di + 1)
+    let mut deduced = pattern.as_bytes().to_vec();
+    for (i, c) in row.forced() {
+        deduced[i] = c as u8;
+    }
+    let mut out = format!("{} arrangements\n", row.count());
+    out += &format!("forced: {}\n", String::from_utf8_lossy(&deduced));
+    for arrangement in row.arrangements().take(20) {
+        out += &format!("  {arrangement}\n");
     }
+    Ok(out)
+}
 
-    fn cached(state: &mut State, x: usize, y: usize) -> u64 {
-        if let Some(result) = state.get_cache((x, y)) {
-            return result;
+fn main() -> anyhow::Result<()> {
+    //`day12 --row "?###???????? 3,2,1"` solves a single row instead, `--unfold N` unfolds
+    //N times rather than 5 (or rather than not at all for a single row), and `--rows`
+    //prints every row's count as well as the total
+    let mut row = None;
+    let mut unfold_by = None;
+    let mut per_row = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--row" => row = Some(args.next().context("--row needs a row")?),
+            "--unfold" => {
+                unfold_by = Some(
+                    args.next()
+                        .context("--unfold needs a factor")?
+                        .parse()
+                        .context("--unfold factor is a number")?,
+                )
+            }
+            "--rows" => per_row = true,
+            _ => anyhow::bail!("unexpected argument {arg}"),
         }
-        let result: u64 = n_matches_from_index(state, x, y);
-        state.set_cache((x, y), result);
-        result
     }
+    anyhow::ensure!(unfold_by != Some(0), "--unfold factor must be at least 1");
 
-    n_matches_from_index(&mut State::new(pattern, description), 0, 0)
-}
+    if let Some(line) = row {
+        print!("{}", describe_row(&line, unfold_by)?);
+        return Ok(());
+    }
+    let unfold_by = unfold_by.unwrap_or(5);
 
-fn main() -> anyhow::Result<()> {
     let input = std::fs::read_to_string("inputs/day12.txt")?;
-    let [part1, part2] = input
+    let counts: Vec<(u64, BigUint)> = input
         .par_lines()
         .map(|line| {
-            let (pattern, description) = line.split_once(' ').unwrap();
-            let description = description
-                .split(',')
-                .map(str::parse)
-                .collect::<Result<Vec<usize>, _>>()
-                .unwrap();
-
-            let unfolded = std::iter::repeat(pattern).take(5).collect_vec().join("?");
-            let repeated = description.repeat(5);
-            [
+            let (pattern, description) = parse_line(line).unwrap();
+            (
                 n_matches(pattern, &description),
-                n_matches(&unfolded, &repeated),
-            ]
+                n_unfolded_matches(pattern, &description, unfold_by),
+            )
         })
-        .reduce(|| [0, 0], |a, b| [a[0] + b[0], a[1] + b[1]]);
+        .collect();
+    let part1: u64 = counts.iter().map(|(folded, _)| folded).sum();
+    let part2: BigUint = counts.iter().map(|(_, unfolded)| unfolded).sum();
+
+    if per_row {
+        for (line, (folded, unfolded)) in input.lines().zip(&counts) {
+            println!("{line:40} {folded:>6} {unfolded:>30}");
+        }
+        if let Some((line, (_, unfolded))) = input
+            .lines()
+            .zip(&counts)
+            .max_by_key(|(_, (_, unfolded))| unfolded)
+            .filter(|_| !part2.is_zero())
+        {
+            let share = unfolded * 10000u32 / &part2;
+            println!(
+                "largest: {line} with {unfolded}, {}.{:02}% of the total",
+                &share / 100u32,
+                share % 100u32
+            );
+        }
+    }
 
     println!("12.1: {}", part1);
     println!("12.2: {}", part2);
@@ -145,4 +132,46 @@ mod test {
         assert_eq!(n_matches("???.###", &[1, 1, 3]), 1);
         assert_eq!(n_matches(".??..??...?##.", &[1, 1, 3]), 4);
     }
+
+    #[test]
+    fn test_describe_row() {
+        let folded = describe_row(".??..??...?##. 1,1,3", None).unwrap();
+        assert!(folded.starts_with("4 arrangements\nforced: .??..??...###.\n"));
+        let unfolded = describe_row(".??..??...?##. 1,1,3", Some(5)).unwrap();
+        assert!(unfolded.starts_with("16384 arrangements\n"));
+    }
+
+    #[test]
+    fn test_unfolded_matches() {
+        let example = [
+            ("???.###", vec![1, 1, 3]),
+            (".??..??...?##.", vec![1, 1, 3]),
+            ("?#?#?#?#?#?#?#?", vec![1, 3, 1, 6]),
+            ("????.#...#...", vec![4, 1, 1]),
+            ("????.######..#####.", vec![1, 6, 5]),
+            ("?###????????", vec![3, 2, 1]),
+        ];
+        let total: BigUint = example
+            .iter()
+            .map(|(pattern, groups)| n_unfolded_matches(pattern, groups, 5))
+            .sum();
+        assert_eq!(total, BigUint::from(525152u32));
+        assert_eq!(
+            n_unfolded_matches("?###????????", &[3, 2, 1], 5),
+            BigUint::from(506250u32)
+        );
+        assert_eq!(
+            n_unfolded_matches("???.###", &[1, 1, 3], 1),
+            BigUint::from(n_matches("???.###", &[1, 1, 3]))
+        );
+
+        //the `##` ending each copy can only be its own last group, so the copies can't
+        //trade groups and each one independently puts its middle `1` in one of 3 places
+        let independent = n_unfolded_matches("#.???.##", &[1, 1, 2], 100);
+        assert_eq!(independent, BigUint::from(3u32).pow(100));
+        assert!(independent > BigUint::from(u64::MAX));
+        assert!(
+            n_unfolded_matches("????.######..#####.", &[1, 6, 5], 30) > BigUint::from(u64::MAX)
+        );
+    }
 }
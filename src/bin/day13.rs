@@ -9,64 +9,261 @@ struct Grid {
     cells: Vec<Cell>,
     width: usize,
     height: usize,
+    //each row as a bitmask of its rocks, split into 64-bit words
+    rows: Vec<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Axis {
+    //a line between two rows
+    Horizontal,
+    //a line between two columns
+    Vertical,
+}
+
+//a cell which differs from its mirror image, paired with that image, as (x, y)
+type Smudge = ((usize, usize), (usize, usize));
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Reflection {
+    axis: Axis,
+    //the number of rows above or columns left of the line
+    before: usize,
+    smudges: Vec<Smudge>,
+}
+
+impl Reflection {
+    fn summary(&self) -> usize {
+        match self.axis {
+            Axis::Horizontal => 100 * self.before,
+            Axis::Vertical => self.before,
+        }
+    }
+}
+
+//whole-pattern symmetries, besides reflection across a line between rows or columns
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Symmetry {
+    //reflection across the line from the top left corner to the bottom right
+    MainDiagonal,
+    //reflection across the line from the top right corner to the bottom left
+    AntiDiagonal,
+    HalfTurn,
+    QuarterTurn,
+}
+
+impl Symmetry {
+    const ALL: [Symmetry; 4] = [
+        Symmetry::MainDiagonal,
+        Symmetry::AntiDiagonal,
+        Symmetry::HalfTurn,
+        Symmetry::QuarterTurn,
+    ];
+
+    //only turning halfway round keeps a pattern that isn't square the same shape
+    fn applies_to(self, grid: &Grid) -> bool {
+        self == Symmetry::HalfTurn || grid.width == grid.height
+    }
+
+    //the pattern after this transformation
+    fn apply(self, grid: &Grid) -> Grid {
+        match self {
+            Symmetry::MainDiagonal => grid.transpose(),
+            Symmetry::AntiDiagonal => grid.transpose().flip_rows().flip_columns(),
+            Symmetry::HalfTurn => grid.flip_rows().flip_columns(),
+            Symmetry::QuarterTurn => grid.transpose().flip_columns(),
+        }
+    }
+
+    //where the transformation moves the cell at (x, y)
+    fn image(self, (x, y): (usize, usize), grid: &Grid) -> (usize, usize) {
+        let (right, bottom) = (grid.width - 1, grid.height - 1);
+        match self {
+            Symmetry::MainDiagonal => (y, x),
+            Symmetry::AntiDiagonal => (bottom - y, right - x),
+            Symmetry::HalfTurn => (right - x, bottom - y),
+            Symmetry::QuarterTurn => (bottom - y, x),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Symmetric {
+    symmetry: Symmetry,
+    //one smallest set of cells to flip, as (x, y), for the pattern to have the symmetry
+    smudges: Vec<(usize, usize)>,
 }
 
 impl Grid {
     fn new(cells: Vec<Cell>, width: usize) -> Self {
         let height = cells.len() / width;
+        let words = width.div_ceil(64);
+        let mut rows = vec![0; words * height];
+        for (i, cell) in cells.iter().enumerate() {
+            if *cell == Cell::Rock {
+                let (y, x) = (i / width, i % width);
+                rows[y * words + x / 64] |= 1 << (x % 64);
+            }
+        }
         Self {
             cells,
             width,
             height,
+            rows,
         }
     }
 
     fn transpose(&self) -> Self {
         let mut cells = vec![Cell::Ash; self.cells.len()];
         transpose::transpose(&self.cells, &mut cells, self.width, self.height);
-        Self {
-            cells,
-            width: self.height,
-            height: self.width,
-        }
+        Self::new(cells, self.height)
+    }
+
+    //upside down
+    fn flip_rows(&self) -> Self {
+        let cells = self.cells.chunks(self.width).rev().flatten().copied();
+        Self::new(cells.collect(), self.width)
     }
 
-    fn row(&self, i: usize) -> &[Cell] {
-        &self.cells[self.width * i..(self.width * (i + 1))]
+    //left to right
+    fn flip_columns(&self) -> Self {
+        let rows = self.cells.chunks(self.width);
+        let cells = rows.flat_map(|row| row.iter().rev()).copied();
+        Self::new(cells.collect(), self.width)
+    }
+
+    fn cell(&self, (x, y): (usize, usize)) -> Cell {
+        self.cells[y * self.width + x]
+    }
+
+    fn row(&self, i: usize) -> &[u64] {
+        let words = self.width.div_ceil(64);
+        &self.rows[words * i..words * (i + 1)]
     }
 
     fn row_diff(&self, r1: usize, r2: usize) -> usize {
         self.row(r1)
             .iter()
             .zip(self.row(r2))
-            .filter(|(a, b)| a != b)
-            .count()
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum()
     }
 
-    fn row_mirror_line(&self) -> Option<usize> {
-        let candidates: Vec<usize> = (0..(self.height - 1))
-            .filter(|&i| {
-                (0..=i.min(self.height - i - 2)).all(|j| self.row(i - j) == self.row(i + 1 + j))
+    //the columns at which two rows differ
+    fn row_differences(&self, r1: usize, r2: usize) -> impl Iterator<Item = usize> + '_ {
+        self.row(r1)
+            .iter()
+            .zip(self.row(r2))
+            .enumerate()
+            .flat_map(|(w, (a, b))| {
+                let mut diff = a ^ b;
+                std::iter::from_fn(move || {
+                    let bit = (diff != 0).then(|| diff.trailing_zeros() as usize)?;
+                    diff &= diff - 1;
+                    Some(64 * w + bit)
+                })
             })
-            .collect();
-        candidates.first().copied()
     }
 
-    fn row_mirror_line_with_smudge(&self) -> Option<usize> {
-        let candidates: Vec<usize> = (0..(self.height - 1))
-            .filter(|&i| {
-                (0..=i.min(self.height - i - 2))
-                    .map(|j| self.row_diff(i - j, i + 1 + j))
-                    .sum::<usize>()
-                    == 1
+    //every line between rows with exactly `smudges` differences across it, as the line's
+    //position and the (x, y) cell pairs that differ
+    fn row_mirror_lines(&self, smudges: usize) -> impl Iterator<Item = (usize, Vec<Smudge>)> + '_ {
+        (1..self.height).filter_map(move |line| {
+            let pairs = (0..line.min(self.height - line)).map(|j| (line - 1 - j, line + j));
+            let mut total = 0;
+            for (a, b) in pairs.clone() {
+                total += self.row_diff(a, b);
+                if total > smudges {
+                    return None;
+                }
+            }
+            (total == smudges).then(|| {
+                let cells = pairs
+                    .flat_map(|(a, b)| self.row_differences(a, b).map(move |x| ((x, a), (x, b))))
+                    .collect();
+                (line, cells)
             })
-            .collect();
-        candidates.first().copied()
+        })
     }
 }
 
-fn main() -> anyhow::Result<()> {
-    let input = std::fs::read_to_string("inputs/day13.txt")?;
+//every horizontal and vertical line the grid reflects across with exactly `smudges` cells
+//differing from their mirror images
+fn reflections(grid: &Grid, smudges: usize) -> Vec<Reflection> {
+    let horizontal = grid
+        .row_mirror_lines(smudges)
+        .map(|(before, smudges)| Reflection {
+            axis: Axis::Horizontal,
+            before,
+            smudges,
+        });
+    let transposed = grid.transpose();
+    let vertical = transposed
+        .row_mirror_lines(smudges)
+        .map(|(before, smudges)| Reflection {
+            axis: Axis::Vertical,
+            before,
+            smudges: smudges
+                .into_iter()
+                .map(|((y1, x1), (y2, x2))| ((x1, y1), (x2, y2)))
+                .collect(),
+        });
+    horizontal.chain(vertical).collect()
+}
+
+//the symmetries the grid has once exactly `smudges` cells are flipped, and no fewer
+fn symmetries(grid: &Grid, smudges: usize) -> Vec<Symmetric> {
+    Symmetry::ALL
+        .into_iter()
+        .filter(|symmetry| symmetry.applies_to(grid))
+        .filter_map(|symmetry| {
+            //flipping a cell fixes at most two mismatches, it and its image, so too many
+            //mismatches rule the symmetry out without looking at the orbits
+            let image = symmetry.apply(grid);
+            let mismatches = (grid.cells.iter().zip(&image.cells))
+                .filter(|(a, b)| a != b)
+                .count();
+            if mismatches.div_ceil(2) > smudges {
+                return None;
+            }
+
+            //each orbit of cells under the symmetry must end up all rock or all ash, so the
+            //minority in each is flipped, keeping the orbit's first cell on a tie
+            let mut seen = vec![false; grid.cells.len()];
+            let mut flips = Vec::new();
+            for start in 0..grid.cells.len() {
+                if seen[start] {
+                    continue;
+                }
+                let start = (start % grid.width, start / grid.width);
+                let orbit = std::iter::successors(Some(start), |&p| {
+                    Some(symmetry.image(p, grid)).filter(|&q| q != start)
+                })
+                .collect::<Vec<_>>();
+                let rocks = orbit.iter().filter(|&&p| grid.cell(p) == Cell::Rock);
+                let minority = match 2 * rocks.count() {
+                    r if r > orbit.len() => Cell::Ash,
+                    r if r < orbit.len() => Cell::Rock,
+                    _ if grid.cell(start) == Cell::Rock => Cell::Ash,
+                    _ => Cell::Rock,
+                };
+                for &p in &orbit {
+                    seen[p.1 * grid.width + p.0] = true;
+                }
+                flips.extend(orbit.into_iter().filter(|&p| grid.cell(p) == minority));
+                if flips.len() > smudges {
+                    return None;
+                }
+            }
+            (flips.len() == smudges).then_some(Symmetric {
+                symmetry,
+                smudges: flips,
+            })
+        })
+        .collect()
+}
+
+fn parse_grids(input: &str) -> Vec<Grid> {
     let mut grids: Vec<Grid> = Vec::new();
     let mut current = Vec::new();
     let mut width = 0;
@@ -88,25 +285,268 @@ fn main() -> anyhow::Result<()> {
     if !current.is_empty() {
         grids.push(Grid::new(current, width));
     }
+    grids
+}
 
-    let mut part1 = 0;
-    let mut part2 = 0;
-    for grid in grids {
-        let tgrid = grid.transpose();
-        part1 += tgrid.row_mirror_line().map(|x| x + 1).unwrap_or(0);
-        part1 += grid.row_mirror_line().map(|x| x + 1).unwrap_or(0) * 100;
-        part2 += tgrid
-            .row_mirror_line_with_smudge()
-            .map(|x| x + 1)
-            .unwrap_or(0);
-        part2 += grid
-            .row_mirror_line_with_smudge()
-            .map(|x| x + 1)
-            .unwrap_or(0)
-            * 100;
-    }
-    println!("13.1: {}", part1);
-    println!("13.2: {}", part2);
+fn main() -> anyhow::Result<()> {
+    //`day13 --smudges K` lists every reflection line and symmetry with exactly K smudges in
+    //each pattern
+    let mut args = std::env::args().skip(1);
+    let smudges = match args.next().as_deref() {
+        Some("--smudges") => Some(args.next().map(|k| k.parse::<usize>()).transpose()?),
+        _ => None,
+    };
+
+    let input = std::fs::read_to_string("inputs/day13.txt")?;
+    let grids = parse_grids(&input);
+
+    if let Some(smudges) = smudges {
+        let smudges = smudges.unwrap_or(0);
+        for (i, grid) in grids.iter().enumerate() {
+            for reflection in reflections(grid, smudges) {
+                let cells = reflection
+                    .smudges
+                    .iter()
+                    .map(|(a, b)| format!("{a:?}/{b:?}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                println!(
+                    "pattern {i}: {:?} after {} {cells}",
+                    reflection.axis, reflection.before
+                );
+            }
+            for symmetric in symmetries(grid, smudges) {
+                let cells = symmetric.smudges.iter().map(|p| format!("{p:?}"));
+                println!(
+                    "pattern {i}: {:?} {}",
+                    symmetric.symmetry,
+                    cells.collect::<Vec<_>>().join(" ")
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let summarise = |smudges| -> usize {
+        grids
+            .iter()
+            .flat_map(|grid| reflections(grid, smudges))
+            .map(|reflection| reflection.summary())
+            .sum()
+    };
+    println!("13.1: {}", summarise(0));
+    println!("13.2: {}", summarise(1));
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use aoc_2023::xorshift::XorShift;
+
+    use super::*;
+
+    const EXAMPLE: &str = "#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#
+";
+
+    #[test]
+    fn test_reflections() {
+        let grids = parse_grids(EXAMPLE);
+        let found = grids
+            .iter()
+            .map(|grid| reflections(grid, 0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                vec![Reflection {
+                    axis: Axis::Vertical,
+                    before: 5,
+                    smudges: vec![]
+                }],
+                vec![Reflection {
+                    axis: Axis::Horizontal,
+                    before: 4,
+                    smudges: vec![]
+                }],
+            ]
+        );
+
+        let found = grids
+            .iter()
+            .map(|grid| reflections(grid, 1))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                vec![Reflection {
+                    axis: Axis::Horizontal,
+                    before: 3,
+                    smudges: vec![((0, 0), (0, 5))]
+                }],
+                vec![Reflection {
+                    axis: Axis::Horizontal,
+                    before: 1,
+                    smudges: vec![((4, 0), (4, 1))]
+                }],
+            ]
+        );
+    }
+
+    fn random_grid(width: usize, height: usize, rng: &mut XorShift) -> Grid {
+        let cells = (0..width * height)
+            .map(|_| {
+                if rng.below(2) == 0 {
+                    Cell::Rock
+                } else {
+                    Cell::Ash
+                }
+            })
+            .collect();
+        Grid::new(cells, width)
+    }
+
+    #[test]
+    fn test_fixing_smudges() {
+        //flipping every reported smudge turns each line into a perfect reflection
+        let mut rng = XorShift::new(0x9e3779b97f4a7c15);
+        for _ in 0..300 {
+            let width = 1 + rng.below(70);
+            let height = 1 + rng.below(12);
+            let grid = random_grid(width, height, &mut rng);
+            for smudges in 0..3 {
+                for reflection in reflections(&grid, smudges) {
+                    assert_eq!(reflection.smudges.len(), smudges);
+                    let mut fixed = grid.cells.clone();
+                    for &((x, y), _) in &reflection.smudges {
+                        fixed[y * width + x] = match fixed[y * width + x] {
+                            Cell::Rock => Cell::Ash,
+                            Cell::Ash => Cell::Rock,
+                        };
+                    }
+                    let perfect = reflections(&Grid::new(fixed, width), 0);
+                    assert!(perfect
+                        .iter()
+                        .any(|r| r.axis == reflection.axis && r.before == reflection.before));
+                }
+            }
+        }
+    }
+
+    fn grid(rows: &str) -> Grid {
+        parse_grids(rows).remove(0)
+    }
+
+    #[test]
+    fn test_symmetries() {
+        let found = |rows, smudges| {
+            symmetries(&grid(rows), smudges)
+                .into_iter()
+                .map(|s| (s.symmetry, s.smudges))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            found("#..\n.#.\n..#\n", 0),
+            [
+                (Symmetry::MainDiagonal, vec![]),
+                (Symmetry::AntiDiagonal, vec![]),
+                (Symmetry::HalfTurn, vec![]),
+            ]
+        );
+        assert_eq!(
+            found("##.\n.#.\n..#\n", 1),
+            [
+                (Symmetry::MainDiagonal, vec![(0, 1)]),
+                (Symmetry::AntiDiagonal, vec![(2, 1)]),
+                (Symmetry::HalfTurn, vec![(1, 2)]),
+            ]
+        );
+        assert_eq!(
+            found(".#.\n###\n.#.\n", 0),
+            [
+                (Symmetry::MainDiagonal, vec![]),
+                (Symmetry::AntiDiagonal, vec![]),
+                (Symmetry::HalfTurn, vec![]),
+                (Symmetry::QuarterTurn, vec![]),
+            ]
+        );
+        //a pinwheel turns onto itself without any reflection
+        assert_eq!(
+            found("##.#\n...#\n#...\n#.##\n", 0),
+            [
+                (Symmetry::HalfTurn, vec![]),
+                (Symmetry::QuarterTurn, vec![]),
+            ]
+        );
+        assert_eq!(
+            found("#..#\n...#\n#...\n#.##\n", 1),
+            [
+                (Symmetry::HalfTurn, vec![(2, 3)]),
+                (Symmetry::QuarterTurn, vec![(1, 0)]),
+            ]
+        );
+        //only half turns apply to patterns which aren't square
+        assert_eq!(found("#..\n..#\n", 0), [(Symmetry::HalfTurn, vec![])]);
+    }
+
+    #[test]
+    fn test_symmetries_brute_force() {
+        let mut rng = XorShift::new(0x2545f4914f6cdd1d);
+        for (width, height, count) in [(3, 3, 40), (2, 5, 40), (4, 4, 2)] {
+            for _ in 0..count {
+                let grid = random_grid(width, height, &mut rng);
+                for symmetry in Symmetry::ALL {
+                    if !symmetry.applies_to(&grid) {
+                        continue;
+                    }
+                    let image = symmetry.apply(&grid);
+                    for y in 0..height {
+                        for x in 0..width {
+                            let moved = symmetry.image((x, y), &grid);
+                            assert_eq!(image.cell(moved), grid.cell((x, y)));
+                        }
+                    }
+
+                    //the fewest flips after which the pattern has the symmetry
+                    let fewest = (0u32..1 << (width * height))
+                        .filter(|flips| {
+                            let mut cells = grid.cells.clone();
+                            for (i, cell) in cells.iter_mut().enumerate() {
+                                if flips & 1 << i != 0 {
+                                    *cell = match cell {
+                                        Cell::Rock => Cell::Ash,
+                                        Cell::Ash => Cell::Rock,
+                                    };
+                                }
+                            }
+                            let flipped = Grid::new(cells, width);
+                            symmetry.apply(&flipped) == flipped
+                        })
+                        .map(u32::count_ones)
+                        .min()
+                        .unwrap() as usize;
+                    for smudges in 0..=width * height / 2 {
+                        let found = symmetries(&grid, smudges)
+                            .into_iter()
+                            .any(|s| s.symmetry == symmetry);
+                        assert_eq!(found, smudges == fewest);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,13 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    hash::{BuildHasher, Hash, RandomState},
+    ops::{Index, IndexMut, Range},
+    rc::Rc,
+    str::FromStr,
+};
 
+use anyhow::Context;
 use itertools::Itertools;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -10,6 +18,115 @@ enum Cell {
     Ground,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Edge {
+    North,
+    West,
+    South,
+    East,
+}
+
+impl TryFrom<char> for Edge {
+    type Error = anyhow::Error;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        Ok(match c.to_ascii_uppercase() {
+            'N' => Edge::North,
+            'W' => Edge::West,
+            'S' => Edge::South,
+            'E' => Edge::East,
+            _ => anyhow::bail!("{c:?} is not one of N, W, S or E"),
+        })
+    }
+}
+
+//a sequence of tilts, each towards an edge, which together make up one cycle
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct TiltProgram(Vec<Edge>);
+
+impl TiltProgram {
+    fn spin_cycle() -> Self {
+        TiltProgram(vec![Edge::North, Edge::West, Edge::South, Edge::East])
+    }
+}
+
+impl FromStr for TiltProgram {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tilts = s
+            .chars()
+            .map(Edge::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        anyhow::ensure!(!tilts.is_empty(), "a tilt program needs at least one tilt");
+        Ok(TiltProgram(tilts))
+    }
+}
+
+//the grids a program passes through settle into a loop: after `pre_period` runs, every
+//`period` runs give the same grid again
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Repeats {
+    pre_period: usize,
+    period: usize,
+}
+
+//a mutable view of every `stride`th cell of a slice, from its start or, reversed, from its
+//end, such as a column of a grid read upwards
+struct Strided<'a> {
+    cells: &'a mut [Cell],
+    stride: usize,
+    len: usize,
+    reversed: bool,
+}
+
+impl<'a> Strided<'a> {
+    fn new(cells: &'a mut [Cell], stride: usize) -> Self {
+        let len = cells.len().div_ceil(stride);
+        Self {
+            cells,
+            stride,
+            len,
+            reversed: false,
+        }
+    }
+
+    fn rev(self) -> Self {
+        Self {
+            reversed: !self.reversed,
+            ..self
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn offset(&self, i: usize) -> usize {
+        assert!(i < self.len);
+        if self.reversed {
+            (self.len - 1 - i) * self.stride
+        } else {
+            i * self.stride
+        }
+    }
+}
+
+impl Index<usize> for Strided<'_> {
+    type Output = Cell;
+
+    fn index(&self, i: usize) -> &Cell {
+        &self.cells[self.offset(i)]
+    }
+}
+
+impl IndexMut<usize> for Strided<'_> {
+    fn index_mut(&mut self, i: usize) -> &mut Cell {
+        let offset = self.offset(i);
+        &mut self.cells[offset]
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash)]
 struct Grid {
     cells: Vec<Cell>,
@@ -62,121 +179,450 @@ impl Grid {
         self.cells[self.width * y..(self.width * (y + 1))].iter()
     }
 
-    fn row_mut(&mut self, y: usize) -> impl Iterator<Item = &mut Cell> {
-        assert!(y < self.height);
-        self.cells[self.width * y..(self.width * (y + 1))].iter_mut()
-    }
-
     fn column(&self, x: usize) -> impl Iterator<Item = &Cell> {
         assert!(x < self.width);
         (0..self.height).map(move |y| &self.cells[self.width * y + x])
     }
 
-    fn column_mut(&mut self, x: usize) -> impl Iterator<Item = &mut Cell> {
+    fn row_mut(&mut self, y: usize) -> Strided<'_> {
+        let row = self.cells.chunks_exact_mut(self.width).nth(y).unwrap();
+        Strided::new(row, 1)
+    }
+
+    fn column_mut(&mut self, x: usize) -> Strided<'_> {
         assert!(x < self.width);
-        assert!(self.width * (self.height - 1) + x < self.cells.len());
-
-        //SAFETY: x and y are both within bounds, so .add() should also always be in bounds
-        //we know that self lives for 'a, and therefore so does the reference to Cell.
-        //if self.height is 0 then this will not yield any references, and if self.height is nonzero
-        //then all of our indexes are disjoint, provided x < self.width, which is asserted.
-        //we do an additonal assert to ensure that the internal cells buffer is of the correct length
-        (0..self.height)
-            .map(move |y| unsafe { &mut *self.cells.as_mut_ptr().add(self.width * y + x) })
-    }
-
-    //splits the given axis at `Rock`s, in each chunk moving either `Boulder` or `Ground` to
-    //the start, depending on which is `heavier`
-    fn tilt<'a>(axis: impl Iterator<Item = &'a mut Cell>, heavier: Cell) {
-        let lighter = match heavier {
-            Cell::Rock => panic!(),
-            Cell::Boulder => Cell::Ground,
-            Cell::Ground => Cell::Boulder,
+        Strided::new(&mut self.cells[x..], self.width)
+    }
+
+    //the line `i` along the direction of a tilt towards `edge`, starting at that edge
+    fn line_towards(&mut self, edge: Edge, i: usize) -> Strided<'_> {
+        match edge {
+            Edge::North => self.column_mut(i),
+            Edge::South => self.column_mut(i).rev(),
+            Edge::West => self.row_mut(i),
+            Edge::East => self.row_mut(i).rev(),
+        }
+    }
+
+    //rolls each `Boulder` towards the start of the line until it hits a `Rock`, another
+    //`Boulder` or the edge
+    fn tilt(mut line: Strided) {
+        let mut free = 0;
+        for i in 0..line.len() {
+            match line[i] {
+                Cell::Rock => free = i + 1,
+                Cell::Boulder => {
+                    line[i] = Cell::Ground;
+                    line[free] = Cell::Boulder;
+                    free += 1;
+                }
+                Cell::Ground => (),
+            }
+        }
+    }
+
+    fn tilt_towards(&mut self, edge: Edge) {
+        let lines = match edge {
+            Edge::North | Edge::South => self.width,
+            Edge::West | Edge::East => self.height,
+        };
+        for i in 0..lines {
+            Grid::tilt(self.line_towards(edge, i));
+        }
+    }
+
+    //each boulder weighs as much as the number of rows or columns from it to the far edge
+    fn load(&self, edge: Edge) -> usize {
+        let boulders = |line: &mut dyn Iterator<Item = &Cell>| {
+            line.filter(|&&cell| cell == Cell::Boulder).count()
         };
+        match edge {
+            Edge::North => (0..self.height)
+                .map(|y| boulders(&mut self.row(y)) * (self.height - y))
+                .sum(),
+            Edge::South => (0..self.height)
+                .map(|y| boulders(&mut self.row(y)) * (y + 1))
+                .sum(),
+            Edge::West => (0..self.width)
+                .map(|x| boulders(&mut self.column(x)) * (self.width - x))
+                .sum(),
+            Edge::East => (0..self.width)
+                .map(|x| boulders(&mut self.column(x)) * (x + 1))
+                .sum(),
+        }
+    }
+}
+
+//a grid of bits, one row after another, each row padded out to whole words
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Bits {
+    width: usize,
+    height: usize,
+    words: usize,
+    bits: Vec<u64>,
+}
 
-        let mut column = axis.collect_vec();
-        let sections = column.split_mut(|x| **x == Cell::Rock).collect_vec();
-        for section in sections {
-            let n_rising = section.iter().filter(|x| ***x == heavier).count();
-            for section in section.iter_mut().take(n_rising) {
-                **section = heavier;
+impl Bits {
+    fn new(width: usize, height: usize) -> Self {
+        let words = width.div_ceil(64);
+        Self {
+            width,
+            height,
+            words,
+            bits: vec![0; words * height],
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.bits[y * self.words + x / 64] & 1 << (x % 64) != 0
+    }
+
+    fn set(&mut self, x: usize, y: usize) {
+        self.bits[y * self.words + x / 64] |= 1 << (x % 64);
+    }
+
+    //each word of row y which overlaps the range, with a mask of the overlapping bits
+    fn masks(&self, y: usize, range: Range<usize>) -> impl Iterator<Item = (usize, u64)> {
+        let row = y * self.words;
+        (range.start / 64..range.end.div_ceil(64)).map(move |w| {
+            let lo = range.start.max(64 * w) - 64 * w;
+            let hi = range.end.min(64 * w + 64) - 64 * w;
+            let mask = if hi - lo == 64 {
+                !0
+            } else {
+                ((1 << (hi - lo)) - 1) << lo
+            };
+            (row + w, mask)
+        })
+    }
+
+    fn count(&self, y: usize, range: Range<usize>) -> usize {
+        self.masks(y, range)
+            .map(|(i, mask)| (self.bits[i] & mask).count_ones() as usize)
+            .sum()
+    }
+
+    fn fill(&mut self, y: usize, range: Range<usize>, value: bool) {
+        for (i, mask) in self.masks(y, range) {
+            if value {
+                self.bits[i] |= mask;
+            } else {
+                self.bits[i] &= !mask;
             }
-            for section in section.iter_mut().skip(n_rising) {
-                **section = lighter;
+        }
+    }
+
+    //the (x, y) of every set bit
+    fn ones(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.bits.iter().enumerate().flat_map(move |(i, &word)| {
+            let (y, x) = (i / self.words, 64 * (i % self.words));
+            let mut word = word;
+            std::iter::from_fn(move || {
+                let bit = (word != 0).then(|| word.trailing_zeros() as usize)?;
+                word &= word - 1;
+                Some((x + bit, y))
+            })
+        })
+    }
+
+    //a 64 by 64 block at a time, each transposed in place by swapping ever smaller
+    //quarters, with the bits past the end of each row kept clear
+    fn transpose(&self) -> Self {
+        let mut out = Bits::new(self.height, self.width);
+        let mut block = [0u64; 64];
+        for by in 0..self.height.div_ceil(64) {
+            for bx in 0..self.words {
+                for (r, word) in block.iter_mut().enumerate() {
+                    let y = 64 * by + r;
+                    *word = if y < self.height {
+                        self.bits[y * self.words + bx]
+                    } else {
+                        0
+                    };
+                }
+
+                let (mut j, mut m) = (32, 0x0000_0000_ffff_ffffu64);
+                while j != 0 {
+                    let mut k = 0;
+                    while k < 64 {
+                        let t = ((block[k] >> j) ^ block[k + j]) & m;
+                        block[k] ^= t << j;
+                        block[k + j] ^= t;
+                        k = (k + j + 1) & !j;
+                    }
+                    j >>= 1;
+                    m ^= m << j;
+                }
+
+                for (c, &word) in block.iter().enumerate() {
+                    let x = 64 * bx + c;
+                    if x < self.width {
+                        out.bits[x * out.words + by] = word;
+                    }
+                }
             }
         }
+        out
     }
+}
 
-    fn tilt_north(&mut self) {
-        for i in 0..self.width {
-            Grid::tilt(self.column_mut(i), Cell::Boulder);
+//the runs of cells between cube rocks along each row of a `Bits`. Most fit in one word and
+//are tilted with a popcount and a mask; the rest go through the slower range operations
+#[derive(Default)]
+struct Segments {
+    //(index of the word, mask of the segment's bits)
+    within: Vec<(usize, u64)>,
+    across: Vec<(usize, Range<usize>)>,
+}
+
+impl Segments {
+    fn new(cubes: &Bits) -> Self {
+        let mut segments = Segments::default();
+        for y in 0..cubes.height {
+            let mut start = 0;
+            for x in 0..=cubes.width {
+                if x < cubes.width && !cubes.get(x, y) {
+                    continue;
+                }
+                //a single free cell can't change when tilted
+                if x - start > 1 && start / 64 == (x - 1) / 64 {
+                    let (lo, hi) = (start % 64, (x - 1) % 64);
+                    let mask = (u64::MAX << lo) & (u64::MAX >> (63 - hi));
+                    segments.within.push((y * cubes.words + start / 64, mask));
+                } else if x - start > 1 {
+                    segments.across.push((y, start..x));
+                }
+                start = x + 1;
+            }
         }
+        segments
     }
+}
+
+//the cube rocks, which never move, and the runs of cells between them
+struct Cubes {
+    cubes: Bits,
+    rows: Segments,
+    columns: Segments,
+}
 
-    fn tilt_south(&mut self) {
-        for i in 0..self.width {
-            Grid::tilt(self.column_mut(i), Cell::Ground);
+//the rocks as bitsets, so tilting a run of cells between cube rocks is a popcount and a mask
+//rather than a pass over every cell. Round rocks are kept by row, or by column after tilting
+//north or south, and only transposed when the direction changes
+#[derive(Clone)]
+struct BitGrid {
+    round: Bits,
+    by_column: bool,
+    cubes: Rc<Cubes>,
+}
+
+//grids are the same if their rocks are, however they happen to be stored
+impl PartialEq for BitGrid {
+    fn eq(&self, other: &Self) -> bool {
+        if self.by_column == other.by_column {
+            self.round == other.round
+        } else {
+            *self.rows() == *other.rows()
         }
     }
+}
+
+impl Eq for BitGrid {}
+
+impl Hash for BitGrid {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.rows().bits.hash(state);
+    }
+}
 
-    fn tilt_east(&mut self) {
-        for i in 0..self.height {
-            Grid::tilt(self.row_mut(i), Cell::Ground);
+impl From<&Grid> for BitGrid {
+    fn from(grid: &Grid) -> Self {
+        let mut round = Bits::new(grid.width, grid.height);
+        let mut cubes = Bits::new(grid.width, grid.height);
+        for (i, cell) in grid.cells.iter().enumerate() {
+            let (x, y) = (i % grid.width, i / grid.width);
+            match cell {
+                Cell::Rock => cubes.set(x, y),
+                Cell::Boulder => round.set(x, y),
+                Cell::Ground => (),
+            }
+        }
+        let cubes = Cubes {
+            rows: Segments::new(&cubes),
+            columns: Segments::new(&cubes.transpose()),
+            cubes,
+        };
+        Self {
+            round,
+            by_column: false,
+            cubes: Rc::new(cubes),
         }
     }
+}
+
+impl From<&BitGrid> for Grid {
+    fn from(grid: &BitGrid) -> Self {
+        let round = grid.rows();
+        let cubes = &grid.cubes.cubes;
+        let cells = (0..cubes.height)
+            .flat_map(|y| (0..cubes.width).map(move |x| (x, y)))
+            .map(|(x, y)| match (cubes.get(x, y), round.get(x, y)) {
+                (true, _) => Cell::Rock,
+                (false, true) => Cell::Boulder,
+                (false, false) => Cell::Ground,
+            })
+            .collect();
+        Grid::new(cells, cubes.width)
+    }
+}
 
-    fn tilt_west(&mut self) {
-        for i in 0..self.height {
-            Grid::tilt(self.row_mut(i), Cell::Boulder);
+impl BitGrid {
+    //the round rocks by row, whichever way they're stored
+    fn rows(&self) -> std::borrow::Cow<'_, Bits> {
+        if self.by_column {
+            std::borrow::Cow::Owned(self.round.transpose())
+        } else {
+            std::borrow::Cow::Borrowed(&self.round)
         }
     }
 
-    fn cycle(&mut self) {
-        self.tilt_north();
-        self.tilt_west();
-        self.tilt_south();
-        self.tilt_east();
+    fn tilt_towards(&mut self, edge: Edge) {
+        let by_column = matches!(edge, Edge::North | Edge::South);
+        if by_column != self.by_column {
+            self.round = self.round.transpose();
+            self.by_column = by_column;
+        }
+        let segments = if by_column {
+            &self.cubes.columns
+        } else {
+            &self.cubes.rows
+        };
+        let to_start = matches!(edge, Edge::North | Edge::West);
+
+        //the n rocks in a segment end up in its first or last n bits, which are the bits of
+        //its mask that aren't still in the mask once it's shifted n further along. Segments
+        //come in order, so each word is built up in a register rather than through memory
+        let mut word = None;
+        let (mut before, mut after) = (0, 0);
+        for &(i, mask) in &segments.within {
+            if word != Some(i) {
+                if let Some(j) = word {
+                    self.round.bits[j] = after;
+                }
+                word = Some(i);
+                before = self.round.bits[i];
+                after = before;
+            }
+            let n = (before & mask).count_ones();
+            let rolled = if to_start {
+                mask & !mask.checked_shl(n).unwrap_or(0)
+            } else {
+                mask & !mask.checked_shr(n).unwrap_or(0)
+            };
+            after = (after & !mask) | rolled;
+        }
+        if let Some(j) = word {
+            self.round.bits[j] = after;
+        }
+        for (line, range) in &segments.across {
+            let n = self.round.count(*line, range.clone());
+            if n == 0 || n == range.len() {
+                continue;
+            }
+            self.round.fill(*line, range.clone(), false);
+            let rolled = if to_start {
+                range.start..range.start + n
+            } else {
+                range.end - n..range.end
+            };
+            self.round.fill(*line, rolled, true);
+        }
     }
 
-    fn north_load(&self) -> usize {
-        (0..self.height)
-            .rev()
-            .map(|i| (i + 1, self.row(self.height - i - 1)))
-            .map(|(i, row)| row.filter(|&cell| *cell == Cell::Boulder).count() * i)
+    fn load(&self, edge: Edge) -> usize {
+        let (width, height) = (self.cubes.cubes.width, self.cubes.cubes.height);
+        self.round
+            .ones()
+            .map(|(i, line)| match self.by_column {
+                true => (line, i),
+                false => (i, line),
+            })
+            .map(|(x, y)| match edge {
+                Edge::North => height - y,
+                Edge::South => y + 1,
+                Edge::West => width - x,
+                Edge::East => x + 1,
+            })
             .sum()
     }
 }
 
-fn cycle_repeats(mut grid: Grid) -> (usize, usize, Grid) {
-    let mut grids = HashMap::new();
+//anything a tilt program can be run on
+trait Platform: Clone + Eq + Hash {
+    fn run(&mut self, program: &TiltProgram);
+}
+
+impl Platform for Grid {
+    fn run(&mut self, program: &TiltProgram) {
+        for &edge in &program.0 {
+            self.tilt_towards(edge);
+        }
+    }
+}
+
+impl Platform for BitGrid {
+    fn run(&mut self, program: &TiltProgram) {
+        for &edge in &program.0 {
+            self.tilt_towards(edge);
+        }
+    }
+}
+
+//runs the program until a grid comes round again, returning how long that took and the grid
+//at the start of the loop. Only a hash of each grid is kept, and the loop it suggests is
+//checked by running round it again, so big grids with long lead-ins fit in memory
+fn cycle_repeats<P: Platform>(mut grid: P, program: &TiltProgram) -> (Repeats, P) {
+    let hasher = RandomState::new();
+    let mut seen = HashMap::new();
 
     for i in 0.. {
-        let last_seen = grids.insert(grid.clone(), i);
+        let last_seen = seen.insert(hasher.hash_one(&grid), i);
         if let Some(j) = last_seen {
-            return (j, i, grid);
+            let period = i - j;
+            let mut ahead = grid.clone();
+            for _ in 0..period {
+                ahead.run(program);
+            }
+            if ahead == grid {
+                let repeats = Repeats {
+                    pre_period: j,
+                    period,
+                };
+                return (repeats, grid);
+            }
         }
-        grid.cycle();
+        grid.run(program);
     }
     unreachable!()
 }
 
-fn cycle_n(mut grid: Grid, n: usize) -> Grid {
-    let (first, last, mut cycled_grid) = cycle_repeats(grid.clone());
-    if n <= last {
+//the grid after running the program `n` times, skipping every whole loop
+fn cycle_n<P: Platform>(mut grid: P, program: &TiltProgram, n: usize) -> (P, Repeats) {
+    let (repeats, mut cycled_grid) = cycle_repeats(grid.clone(), program);
+    if n < repeats.pre_period {
         for _ in 0..n {
-            grid.cycle();
+            grid.run(program);
         }
-        return grid;
+        return (grid, repeats);
     }
-    let n = (n - first) % (last - first);
+    let n = (n - repeats.pre_period) % repeats.period;
     for _ in 0..n {
-        cycled_grid.cycle();
+        cycled_grid.run(program);
     }
-    cycled_grid
+    (cycled_grid, repeats)
 }
 
-fn main() -> anyhow::Result<()> {
-    let input = std::fs::read_to_string("inputs/day14.txt")?;
+fn parse_grid(input: &str) -> Grid {
     let width = input.lines().next().unwrap().len();
     let cells = input
         .lines()
@@ -189,13 +635,248 @@ fn main() -> anyhow::Result<()> {
             })
         })
         .collect();
-    let grid = Grid::new(cells, width);
+    Grid::new(cells, width)
+}
+
+fn main() -> anyhow::Result<()> {
+    //`day14 PROGRAM N EDGE`, e.g. `day14 NNESW 1000 E`, runs a tilt program N times and
+    //measures the load against EDGE
+    let args = std::env::args().skip(1).collect_vec();
+    let input = std::fs::read_to_string("inputs/day14.txt")?;
+    let grid = parse_grid(&input);
+
+    if let [program, times, edge] = &args[..] {
+        let program: TiltProgram = program.parse()?;
+        let times = times.parse().context("N is the number of times to run")?;
+        let edge = edge
+            .chars()
+            .exactly_one()
+            .map_err(|_| anyhow::anyhow!("EDGE is one of N, W, S or E"))?
+            .try_into()?;
+        let (grid, repeats) = cycle_n(BitGrid::from(&grid), &program, times);
+        println!(
+            "repeats every {} after the first {}",
+            repeats.period, repeats.pre_period
+        );
+        println!("load against {edge:?}: {}", grid.load(edge));
+        return Ok(());
+    }
+    anyhow::ensure!(args.is_empty(), "usage: day14 [PROGRAM N EDGE]");
+
     let mut p1grid = grid.clone();
-    p1grid.tilt_north();
+    p1grid.tilt_towards(Edge::North);
 
-    println!("14.1: {:?}", p1grid.north_load());
-    println!("14.2: {:?}", cycle_n(grid, 1000000000).north_load());
+    let (cycled, _) = cycle_n(BitGrid::from(&grid), &TiltProgram::spin_cycle(), 1000000000);
+    println!("14.1: {:?}", p1grid.load(Edge::North));
+    println!("14.2: {:?}", cycled.load(Edge::North));
 
-    // println!("14.1: {}", part1);
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use aoc_2023::xorshift::XorShift;
+
+    use super::*;
+
+    const EXAMPLE: &str = "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....
+";
+
+    fn render(grid: &Grid) -> String {
+        grid.cells
+            .chunks(grid.width)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell {
+                        Cell::Rock => '#',
+                        Cell::Boulder => 'O',
+                        Cell::Ground => '.',
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+
+    //cargo +nightly miri test --bin day14
+    #[test]
+    fn test_strided() {
+        let mut grid = parse_grid("O.#\n.O.\n#.O\n.O#\n");
+        let read = |line: &Strided| (0..line.len()).map(|i| line[i]).collect_vec();
+        use Cell::{Boulder as O, Ground as G, Rock as R};
+        assert_eq!(read(&grid.column_mut(0)), [O, G, R, G]);
+        assert_eq!(read(&grid.column_mut(2).rev()), [R, O, G, R]);
+        assert_eq!(read(&grid.row_mut(3)), [G, O, R]);
+        assert_eq!(read(&grid.line_towards(Edge::East, 2)), [O, G, R]);
+        assert_eq!(read(&grid.line_towards(Edge::South, 1)), [O, G, O, G]);
+
+        let mut column = grid.column_mut(1).rev();
+        column[0] = Cell::Rock;
+        column[3] = Cell::Boulder;
+        assert_eq!(render(&grid), "OO#\n.O.\n#.O\n.##");
+
+        //each direction from the same start, so every view and its reverse gets written
+        let grid = parse_grid("O.#.\n.O.O\n#..O\n.O..\n");
+        let tilted = |edge| {
+            let mut grid = grid.clone();
+            grid.tilt_towards(edge);
+            render(&grid)
+        };
+        assert_eq!(tilted(Edge::North), "OO#O\n.O.O\n#...\n....");
+        assert_eq!(tilted(Edge::South), "..#.\nO...\n#O.O\n.O.O");
+        assert_eq!(tilted(Edge::West), "O.#.\nOO..\n#O..\nO...");
+        assert_eq!(tilted(Edge::East), ".O#.\n..OO\n#..O\n...O");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_strided_bounds() {
+        let mut grid = parse_grid("..\n..\n");
+        let column = grid.column_mut(1);
+        let _ = column[2];
+    }
+
+    #[test]
+    fn test_spin_cycle() {
+        let grid = parse_grid(EXAMPLE);
+        let mut tilted = grid.clone();
+        tilted.tilt_towards(Edge::North);
+        assert_eq!(tilted.load(Edge::North), 136);
+
+        let spin = TiltProgram::spin_cycle();
+        let (cycled, repeats) = cycle_n(grid.clone(), &spin, 1000000000);
+        assert_eq!(cycled.load(Edge::North), 64);
+        assert_eq!(
+            repeats,
+            Repeats {
+                pre_period: 3,
+                period: 7
+            }
+        );
+        assert_eq!("nwse".parse::<TiltProgram>().unwrap(), spin);
+        assert!("NWX".parse::<TiltProgram>().is_err());
+        assert!("".parse::<TiltProgram>().is_err());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_programs() {
+        let grid = parse_grid(EXAMPLE);
+        for program in ["N", "NNESW", "EW", "SSWN", "WSEN"] {
+            let program: TiltProgram = program.parse().unwrap();
+            let (repeats, _) = cycle_repeats(grid.clone(), &program);
+
+            //stepping one run at a time agrees with skipping loops, at every edge
+            let mut stepped = grid.clone();
+            for n in 0..repeats.pre_period + 3 * repeats.period {
+                let (skipped, _) = cycle_n(grid.clone(), &program, n);
+                assert!(skipped == stepped, "{program:?} run {n} times");
+                for edge in [Edge::North, Edge::West, Edge::South, Edge::East] {
+                    assert_eq!(skipped.load(edge), stepped.load(edge));
+                }
+                stepped.run(&program);
+            }
+        }
+
+        //a boulder in a corner weighs the most against the edges it touches
+        let mut corner = parse_grid("...\n...\n..O\n");
+        assert_eq!((corner.load(Edge::North), corner.load(Edge::West)), (1, 1));
+        assert_eq!((corner.load(Edge::South), corner.load(Edge::East)), (3, 3));
+        corner.run(&"NW".parse().unwrap());
+        assert_eq!((corner.load(Edge::North), corner.load(Edge::West)), (3, 3));
+        assert_eq!((corner.load(Edge::South), corner.load(Edge::East)), (1, 1));
+    }
+
+    fn random_grid(width: usize, height: usize, rng: &mut XorShift) -> Grid {
+        let cells = (0..width * height)
+            .map(|_| match rng.below(8) {
+                0 => Cell::Rock,
+                1 | 2 => Cell::Boulder,
+                _ => Cell::Ground,
+            })
+            .collect();
+        Grid::new(cells, width)
+    }
+
+    #[test]
+    fn test_bits() {
+        let mut rng = XorShift::new(0x2023_0014);
+        for (width, height) in [(1, 1), (3, 70), (64, 64), (65, 63), (130, 7)] {
+            let grid = random_grid(width, height, &mut rng);
+            let bits = BitGrid::from(&grid).round;
+            let transposed = bits.transpose();
+            assert_eq!((transposed.width, transposed.height), (height, width));
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(transposed.get(y, x), bits.get(x, y));
+                }
+            }
+            assert!(transposed.transpose() == bits);
+
+            let ones = bits.ones().collect_vec();
+            let set = (0..height).map(|y| bits.count(y, 0..width)).sum::<usize>();
+            assert_eq!(ones.len(), set);
+            assert!(ones.iter().all(|&(x, y)| bits.get(x, y)));
+        }
+
+        let mut bits = Bits::new(200, 1);
+        bits.fill(0, 60..140, true);
+        assert_eq!(bits.count(0, 0..200), 80);
+        assert_eq!(bits.count(0, 64..128), 64);
+        bits.fill(0, 63..129, false);
+        assert_eq!(bits.count(0, 0..200), 14);
+        assert!(bits.get(60, 0) && !bits.get(63, 0) && bits.get(129, 0));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_bit_grid() {
+        let mut rng = XorShift::new(0x2023_1400);
+        for (width, height) in [(10, 10), (1, 9), (70, 5), (67, 130)] {
+            let grid = random_grid(width, height, &mut rng);
+            for program in ["NWSE", "NNESW", "S", "EEN"] {
+                let program: TiltProgram = program.parse().unwrap();
+                let mut cells = grid.clone();
+                let mut bits = BitGrid::from(&grid);
+                for _ in 0..5 {
+                    cells.run(&program);
+                    bits.run(&program);
+                    assert!(Grid::from(&bits) == cells);
+                    for edge in [Edge::North, Edge::West, Edge::South, Edge::East] {
+                        assert_eq!(bits.load(edge), cells.load(edge));
+                    }
+                }
+
+                let (cells, repeats) = cycle_n(grid.clone(), &program, 1_000_000_007);
+                let (bits, bit_repeats) = cycle_n(BitGrid::from(&grid), &program, 1_000_000_007);
+                assert!(Grid::from(&bits) == cells);
+                assert_eq!(repeats, bit_repeats);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_bit_grid() {
+        let grid = random_grid(1000, 1000, &mut XorShift::new(0x2023_0014));
+        let start = std::time::Instant::now();
+        let (cycled, repeats) = cycle_n(
+            BitGrid::from(&grid),
+            &TiltProgram::spin_cycle(),
+            4_000_000_000,
+        );
+        println!(
+            "1000x1000: {repeats:?}, load {} in {:?}",
+            cycled.load(Edge::North),
+            start.elapsed()
+        );
+    }
+}
@@ -1,57 +1,509 @@
+use std::{collections::HashSet, fmt::Display};
+
+use anyhow::Context;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Op {
     Insert(usize),
     Delete,
 }
 
-fn hash(s: &str) -> usize {
-    s.as_bytes()
-        .iter()
-        .fold(0, |acc, c| c.wrapping_add(acc).wrapping_mul(17)) as usize
+//one step of the initialization sequence, such as `rn=1` or `cm-`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Step<'a> {
+    label: &'a str,
+    op: Op,
+}
+
+impl<'a> Step<'a> {
+    fn parse(s: &'a str) -> anyhow::Result<Self> {
+        if let Some((label, x)) = s.split_once('=') {
+            let focal_length = x
+                .parse()
+                .with_context(|| format!("bad focal length in {s:?}"))?;
+            Ok(Step {
+                label,
+                op: Op::Insert(focal_length),
+            })
+        } else {
+            let label = s
+                .strip_suffix('-')
+                .with_context(|| format!("{s:?} is neither an insert nor a removal"))?;
+            Ok(Step {
+                label,
+                op: Op::Delete,
+            })
+        }
+    }
+}
+
+impl Display for Step<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.op {
+            Op::Insert(focal_length) => write!(f, "{}={focal_length}", self.label),
+            Op::Delete => write!(f, "{}-", self.label),
+        }
+    }
+}
+
+//the HASH algorithm: for each byte, add it, multiply by `multiplier`, then take the remainder
+//on dividing by `size`. The puzzle's is 17 and 256
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HolidayHash {
+    multiplier: usize,
+    size: usize,
+}
+
+impl Default for HolidayHash {
+    fn default() -> Self {
+        Self {
+            multiplier: 17,
+            size: 256,
+        }
+    }
+}
+
+impl HolidayHash {
+    fn step(&self, h: usize, byte: u8) -> usize {
+        ((h as u128 + byte as u128) * self.multiplier as u128 % self.size as u128) as usize
+    }
+
+    fn hash(&self, s: &str) -> usize {
+        s.bytes().fold(0, |h, byte| self.step(h, byte))
+    }
+
+    //how the distinct labels fall into boxes
+    fn analyse<'a>(&self, labels: impl IntoIterator<Item = &'a str>) -> Occupancy {
+        let mut boxes = vec![0; self.size];
+        for label in labels.into_iter().collect::<HashSet<_>>() {
+            boxes[self.hash(label)] += 1;
+        }
+        Occupancy { boxes }
+    }
+
+    //up to `count` labels of lowercase letters which all land in box `target`, shortest first
+    //and then in alphabetical order, none longer than `max_len`
+    fn colliding_labels(&self, target: usize, count: usize, max_len: usize) -> Vec<String> {
+        assert!(target < self.size);
+        //reaches[n][h] is whether n more letters can take the hash from h to the target
+        let mut reaches = vec![(0..self.size).map(|h| h == target).collect::<Vec<_>>()];
+        for n in 1..=max_len {
+            let next = (0..self.size)
+                .map(|h| (b'a'..=b'z').any(|c| reaches[n - 1][self.step(h, c)]))
+                .collect();
+            reaches.push(next);
+        }
+
+        let mut labels = Vec::new();
+        let mut label = Vec::new();
+        for len in 1..=max_len {
+            self.extend_colliding(&reaches, 0, len, &mut label, &mut labels, count);
+        }
+        labels
+    }
+
+    fn extend_colliding(
+        &self,
+        reaches: &[Vec<bool>],
+        h: usize,
+        remaining: usize,
+        label: &mut Vec<u8>,
+        labels: &mut Vec<String>,
+        count: usize,
+    ) {
+        if labels.len() >= count || !reaches[remaining][h] {
+            return;
+        }
+        if remaining == 0 {
+            labels.push(String::from_utf8(label.clone()).unwrap());
+            return;
+        }
+        for c in b'a'..=b'z' {
+            label.push(c);
+            self.extend_colliding(
+                reaches,
+                self.step(h, c),
+                remaining - 1,
+                label,
+                labels,
+                count,
+            );
+            label.pop();
+        }
+    }
+}
+
+//the number of labels in each box
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Occupancy {
+    boxes: Vec<usize>,
+}
+
+impl Occupancy {
+    //histogram[k] is the number of boxes holding k labels
+    fn histogram(&self) -> Vec<usize> {
+        let longest = self.boxes.iter().copied().max().unwrap_or(0);
+        let mut histogram = vec![0; longest + 1];
+        for &n in &self.boxes {
+            histogram[n] += 1;
+        }
+        histogram
+    }
+
+    //the number of pairs of labels which share a box
+    fn collisions(&self) -> usize {
+        self.boxes.iter().map(|n| n * n.saturating_sub(1) / 2).sum()
+    }
+
+    //the fullest box, and how many labels it holds
+    fn longest_chain(&self) -> (usize, usize) {
+        let (b, &n) = (self.boxes.iter().enumerate())
+            .max_by_key(|&(b, n)| (n, std::cmp::Reverse(b)))
+            .unwrap();
+        (b, n)
+    }
+}
+
+//the boxes of lenses, each lens a label and its focal length, in the order they sit
+struct LensLibrary<'a> {
+    hasher: HolidayHash,
+    boxes: Vec<Vec<(&'a str, usize)>>,
+}
+
+impl<'a> LensLibrary<'a> {
+    fn new() -> Self {
+        Self::with_hasher(HolidayHash::default())
+    }
+
+    fn with_hasher(hasher: HolidayHash) -> Self {
+        Self {
+            hasher,
+            boxes: vec![Vec::new(); hasher.size],
+        }
+    }
+
+    fn apply(&mut self, step: Step<'a>) {
+        let lenses = &mut self.boxes[self.hasher.hash(step.label)];
+        let i = lenses.iter().position(|(label, _)| *label == step.label);
+        match (step.op, i) {
+            (Op::Insert(focal_length), Some(i)) => lenses[i].1 = focal_length,
+            (Op::Insert(focal_length), None) => lenses.push((step.label, focal_length)),
+            (Op::Delete, Some(i)) => {
+                lenses.remove(i);
+            }
+            (Op::Delete, None) => (),
+        }
+    }
+
+    //the box a lens is in and its slot in that box, both from 0
+    fn find(&self, label: &str) -> Option<(usize, usize)> {
+        let b = self.hasher.hash(label);
+        let slot = self.boxes[b].iter().position(|(l, _)| *l == label)?;
+        Some((b, slot))
+    }
+
+    fn contents(&self, b: usize) -> &[(&'a str, usize)] {
+        &self.boxes[b]
+    }
+
+    //the most lenses in any one box
+    fn longest_chain(&self) -> usize {
+        self.boxes.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    //every lens in box order, with its focusing power
+    fn lens_powers(&self) -> impl Iterator<Item = (&'a str, usize)> + '_ {
+        self.boxes.iter().enumerate().flat_map(|(b, lenses)| {
+            lenses
+                .iter()
+                .enumerate()
+                .map(move |(slot, &(label, focal_length))| {
+                    (label, (b + 1) * (slot + 1) * focal_length)
+                })
+        })
+    }
+
+    fn focusing_power(&self) -> usize {
+        self.lens_powers().map(|(_, power)| power).sum()
+    }
+}
+
+//the non-empty boxes, as the puzzle shows them
+impl Display for LensLibrary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (b, lenses) in self.boxes.iter().enumerate() {
+            if lenses.is_empty() {
+                continue;
+            }
+            write!(f, "Box {b}:")?;
+            for (label, focal_length) in lenses {
+                write!(f, " [{label} {focal_length}]")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
 }
+
+fn parse_steps(input: &str) -> anyhow::Result<Vec<Step<'_>>> {
+    input.trim_end().split(',').map(Step::parse).collect()
+}
+
 fn main() -> anyhow::Result<()> {
+    //`day15 --trace` shows the boxes after every step, `day15 --find LABEL` shows where a
+    //lens ended up, `day15 --analyse [MULTIPLIER SIZE]` shows how evenly HASH spreads the
+    //labels, and `day15 --collide BOX COUNT [MULTIPLIER SIZE]` finds labels which land in BOX
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
     let input = std::fs::read_to_string("inputs/day15.txt")?;
-    let part1 = input.split(',').map(hash).sum::<usize>();
+    let steps = parse_steps(&input)?;
 
-    let part2 = input
-        .split(',')
-        .map(|s| {
-            if let Some((s, x)) = s.split_once('=') {
-                (s, Op::Insert(x.parse::<usize>().unwrap()))
-            } else {
-                (s.strip_suffix('-').unwrap(), Op::Delete)
+    let parse_hasher = |args: &[&str]| -> anyhow::Result<HolidayHash> {
+        match args {
+            [] => Ok(HolidayHash::default()),
+            [multiplier, size] => {
+                let hasher = HolidayHash {
+                    multiplier: multiplier.parse().context("bad multiplier")?,
+                    size: size.parse().context("bad table size")?,
+                };
+                anyhow::ensure!(hasher.size > 0, "the table needs at least one box");
+                Ok(hasher)
             }
-        })
-        .fold(vec![Vec::new(); 256], |mut hm, (key, op)| {
-            let v = &mut hm[hash(key)];
-            let i = v.iter().position(|(k, _)| k == &key);
-            match op {
-                Op::Insert(value) => {
-                    if let Some(i) = i {
-                        v[i] = (key, value);
-                    } else {
-                        v.push((key, value));
-                    }
-                }
-                Op::Delete => {
-                    if let Some(i) = i {
-                        v.remove(i);
-                    }
-                }
-            };
-            hm
-        })
-        .into_iter()
-        .enumerate()
-        .map(|(i, hm)| {
-            (i + 1)
-                * hm.into_iter()
-                    .enumerate()
-                    .map(|(j, (_, v))| v * (j + 1))
-                    .sum::<usize>()
-        })
+            _ => anyhow::bail!("give both a multiplier and a table size"),
+        }
+    };
+
+    let mut library = LensLibrary::new();
+    match args[..] {
+        ["--trace"] => {
+            for &step in &steps {
+                library.apply(step);
+                println!("After \"{step}\":\n{library}");
+            }
+            return Ok(());
+        }
+        ["--find", label] => {
+            steps.iter().for_each(|&step| library.apply(step));
+            let (b, slot) = library
+                .find(label)
+                .with_context(|| format!("no lens {label} in any box"))?;
+            let power = library.lens_powers().find(|&(l, _)| l == label).unwrap().1;
+            println!(
+                "{label} is in slot {} of box {b}, focusing power {power}",
+                slot + 1
+            );
+            let contents = library.contents(b).iter();
+            let contents =
+                contents.map(|(label, focal_length)| format!("[{label} {focal_length}]"));
+            println!("Box {b}: {}", contents.collect::<Vec<_>>().join(" "));
+            return Ok(());
+        }
+        ["--analyse", ref hasher @ ..] => {
+            let hasher = parse_hasher(hasher)?;
+            let occupancy = hasher.analyse(steps.iter().map(|step| step.label));
+            for (n, boxes) in occupancy.histogram().into_iter().enumerate() {
+                println!("{boxes:>6} boxes hold {n} labels");
+            }
+            println!("colliding pairs: {}", occupancy.collisions());
+            let (b, n) = occupancy.longest_chain();
+            println!("longest chain: {n} labels in box {b}");
+
+            let mut library = LensLibrary::with_hasher(hasher);
+            let mut longest = 0;
+            for &step in &steps {
+                library.apply(step);
+                longest = longest.max(library.longest_chain());
+            }
+            println!("longest chain while running: {longest} lenses");
+            return Ok(());
+        }
+        ["--collide", b, count, ref hasher @ ..] => {
+            let hasher = parse_hasher(hasher)?;
+            let b: usize = b.parse().context("bad box")?;
+            anyhow::ensure!(b < hasher.size, "there are only {} boxes", hasher.size);
+            let labels = hasher.colliding_labels(b, count.parse().context("bad count")?, 8);
+            for label in labels {
+                println!("{label}");
+            }
+            return Ok(());
+        }
+        [] => (),
+        _ => anyhow::bail!(
+            "usage: day15 [--trace | --find LABEL | --analyse [MULTIPLIER SIZE] \
+             | --collide BOX COUNT [MULTIPLIER SIZE]]"
+        ),
+    }
+
+    let hasher = HolidayHash::default();
+    let part1 = input
+        .trim_end()
+        .split(',')
+        .map(|s| hasher.hash(s))
         .sum::<usize>();
+    steps.iter().for_each(|&step| library.apply(step));
+    let part2 = library.focusing_power();
 
     println!("15.1: {part1}");
     println!("15.2: {part2}");
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
+
+    #[test]
+    fn test_trace() {
+        let mut library = LensLibrary::new();
+        let trace = parse_steps(EXAMPLE)
+            .unwrap()
+            .into_iter()
+            .map(|step| {
+                library.apply(step);
+                format!("After \"{step}\":\n{library}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            trace,
+            r#"After "rn=1":
+Box 0: [rn 1]
+
+After "cm-":
+Box 0: [rn 1]
+
+After "qp=3":
+Box 0: [rn 1]
+Box 1: [qp 3]
+
+After "cm=2":
+Box 0: [rn 1] [cm 2]
+Box 1: [qp 3]
+
+After "qp-":
+Box 0: [rn 1] [cm 2]
+
+After "pc=4":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4]
+
+After "ot=9":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4] [ot 9]
+
+After "ab=5":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4] [ot 9] [ab 5]
+
+After "pc-":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 9] [ab 5]
+
+After "pc=6":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 9] [ab 5] [pc 6]
+
+After "ot=7":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 7] [ab 5] [pc 6]
+"#
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut library = LensLibrary::new();
+        parse_steps(EXAMPLE)
+            .unwrap()
+            .into_iter()
+            .for_each(|step| library.apply(step));
+        assert_eq!(library.find("ab"), Some((3, 1)));
+        assert_eq!(library.find("qp"), None);
+        assert_eq!(library.contents(0), [("rn", 1), ("cm", 2)]);
+        assert_eq!(
+            library.lens_powers().collect::<Vec<_>>(),
+            [("rn", 1), ("cm", 4), ("ot", 28), ("ab", 40), ("pc", 72)]
+        );
+        assert_eq!(library.focusing_power(), 145);
+
+        assert!(Step::parse("ab").is_err());
+        assert!(Step::parse("ab=x").is_err());
+        assert_eq!(Step::parse("ab=12").unwrap().to_string(), "ab=12");
+    }
+
+    #[test]
+    fn test_hash() {
+        let hasher = HolidayHash::default();
+        assert_eq!(hasher.hash("HASH"), 52);
+        let total = EXAMPLE.split(',').map(|s| hasher.hash(s)).sum::<usize>();
+        assert_eq!(total, 1320);
+
+        //the original u8 arithmetic, which the default has to match
+        let wrapping = |s: &str| {
+            s.bytes()
+                .fold(0u8, |acc, c| c.wrapping_add(acc).wrapping_mul(17)) as usize
+        };
+        for label in ["", "rn", "qp", "zzzzzzzz", "cm=2"] {
+            assert_eq!(hasher.hash(label), wrapping(label));
+        }
+
+        let big = HolidayHash {
+            multiplier: usize::MAX,
+            size: usize::MAX - 1,
+        };
+        assert_eq!(big.hash("a"), 97);
+    }
+
+    #[test]
+    fn test_occupancy() {
+        let hasher = HolidayHash::default();
+        let labels = ["rn", "cm", "qp", "pc", "ot", "ab", "rn", "cm"];
+        let occupancy = hasher.analyse(labels);
+        //rn and cm share box 0, pc, ot and ab box 3, and qp is alone in box 1
+        assert_eq!(occupancy.histogram(), [253, 1, 1, 1]);
+        assert_eq!(occupancy.collisions(), 1 + 3);
+        assert_eq!(occupancy.longest_chain(), (3, 3));
+
+        let two = HolidayHash {
+            multiplier: 1,
+            size: 2,
+        };
+        assert_eq!(two.analyse(["a", "b", "c", "bb"]).boxes, [2, 2]);
+    }
+
+    #[test]
+    fn test_colliding_labels() {
+        let hasher = HolidayHash::default();
+        let labels = hasher.colliding_labels(3, 50, 4);
+        assert_eq!(labels.len(), 50);
+        assert!(labels.contains(&"pc".to_string()) && labels.contains(&"ot".to_string()));
+        assert!(labels.iter().all(|label| hasher.hash(label) == 3));
+        assert!(labels
+            .windows(2)
+            .all(|w| (w[0].len(), &w[0]) < (w[1].len(), &w[1])));
+
+        //every label of up to two letters landing in a small table's box is found
+        let small = HolidayHash {
+            multiplier: 7,
+            size: 31,
+        };
+        let brute = (b'a'..=b'z')
+            .map(|c| String::from(c as char))
+            .chain((b'a'..=b'z').flat_map(|c| {
+                (b'a'..=b'z').map(move |d| String::from_iter([c as char, d as char]))
+            }))
+            .filter(|label| small.hash(label) == 12)
+            .collect::<Vec<_>>();
+        assert_eq!(small.colliding_labels(12, usize::MAX, 2), brute);
+
+        //with an even multiplier and table size, odd boxes are never reached
+        let even = HolidayHash {
+            multiplier: 2,
+            size: 16,
+        };
+        assert!(even.colliding_labels(5, 10, 6).is_empty());
+        assert_eq!(even.colliding_labels(6, 1, 6), ["c"]);
+    }
+}
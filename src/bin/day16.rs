@@ -76,56 +76,82 @@ impl Grid {
         }
         self.cells.get(y * self.width + x).copied()
     }
-}
 
-fn energised_tiles(grid: &Grid, start: (usize, usize, Direction)) -> usize {
-    use Cell::*;
-    use Direction::*;
-    let mut visited = HashSet::new();
-    let mut beam_positions = VecDeque::from(vec![start]);
-    while let Some((x, y, d)) = beam_positions.pop_front() {
-        let cell = grid.get(x, y);
-        if cell.is_none() {
-            //went off the edge
-            continue;
-        }
-        if !visited.insert((x, y, d)) {
-            continue;
+    //where a beam entering the tile at (x, y) heading `d` goes next, if it stays on the grid
+    fn next(&self, (x, y, d): (usize, usize, Direction)) -> [Option<(usize, usize, Direction)>; 2] {
+        use Cell::*;
+        use Direction::*;
+        let step = |d| {
+            let (x, y) = match d {
+                North => (x, y.checked_sub(1)?),
+                East => (x + 1, y),
+                South => (x, y + 1),
+                West => (x.checked_sub(1)?, y),
+            };
+            self.get(x, y).map(|_| (x, y, d))
+        };
+        let Some(cell) = self.get(x, y) else {
+            return [None, None];
         };
-        let cell = cell.unwrap();
         match (d, cell) {
             (North, Empty)
             | (North, VerticalSplitter)
             | (East, NorthEastMirror)
-            | (West, SouthEastMirror) => {
-                if let Some(y) = y.checked_sub(1) {
-                    beam_positions.push_back((x, y, North));
-                }
-            }
+            | (West, SouthEastMirror) => [step(North), None],
             (North, NorthEastMirror)
             | (East, Empty)
             | (East, HorizontalSplitter)
-            | (South, SouthEastMirror) => beam_positions.push_back((x + 1, y, East)),
+            | (South, SouthEastMirror) => [step(East), None],
             (East, SouthEastMirror)
             | (South, Empty)
             | (South, VerticalSplitter)
-            | (West, NorthEastMirror) => beam_positions.push_back((x, y + 1, South)),
+            | (West, NorthEastMirror) => [step(South), None],
             (North, SouthEastMirror)
             | (South, NorthEastMirror)
             | (West, Empty)
-            | (West, HorizontalSplitter) => {
-                if let Some(x) = x.checked_sub(1) {
-                    beam_positions.push_back((x, y, West));
-                }
-            }
-            (North, HorizontalSplitter) | (South, HorizontalSplitter) => {
-                beam_positions.extend([(x, y, West), (x, y, East)])
-            }
-            (East, VerticalSplitter) | (West, VerticalSplitter) => {
-                beam_positions.extend([(x, y, North), (x, y, South)])
-            }
+            | (West, HorizontalSplitter) => [step(West), None],
+            (North, HorizontalSplitter) | (South, HorizontalSplitter) => [step(West), step(East)],
+            (East, VerticalSplitter) | (West, VerticalSplitter) => [step(North), step(South)],
         }
     }
+
+    //every beam state numbered, as (y * width + x) * 4 + direction
+    fn state(&self, (x, y, d): (usize, usize, Direction)) -> usize {
+        (y * self.width + x) * 4 + d as usize
+    }
+
+    fn next_states(&self, state: usize) -> [Option<usize>; 2] {
+        let tile = state / 4;
+        let d = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ][state % 4];
+        self.next((tile % self.width, tile / self.width, d))
+            .map(|next| next.map(|next| self.state(next)))
+    }
+
+    //the beams from every edge tile, heading into the grid
+    fn edge_starts(&self) -> Vec<(usize, usize, Direction)> {
+        let mut starts = Vec::new();
+        starts.extend((0..self.width).map(|x| (x, self.height - 1, Direction::North)));
+        starts.extend((0..self.height).map(|y| (0, y, Direction::East)));
+        starts.extend((0..self.width).map(|x| (x, 0, Direction::South)));
+        starts.extend((0..self.height).map(|y| (self.width - 1, y, Direction::West)));
+        starts
+    }
+}
+
+fn energised_tiles(grid: &Grid, start: (usize, usize, Direction)) -> usize {
+    let mut visited = HashSet::new();
+    let mut beam_positions = VecDeque::from(vec![start]);
+    while let Some(beam) = beam_positions.pop_front() {
+        if !visited.insert(beam) {
+            continue;
+        };
+        beam_positions.extend(grid.next(beam).into_iter().flatten());
+    }
     visited
         .into_iter()
         .map(|(a, b, _)| (a, b))
@@ -134,8 +160,213 @@ fn energised_tiles(grid: &Grid, start: (usize, usize, Direction)) -> usize {
         .count()
 }
 
-fn main() -> anyhow::Result<()> {
-    let input = std::fs::read_to_string("inputs/day16.txt")?;
+//the most components which keep every tile they energise as a bitset over the whole grid,
+//so memory grows with the grid's area rather than its area times its number of loops
+const MAX_STORED: usize = 64;
+
+//the beam graph condensed into its strongly connected components, which splitters make by
+//sending beams round loops. The tiles a beam energises are those of its component and every
+//component after it, so the biggest loops, which most beams end up in, keep theirs as a bitset
+//and a search from any start stops there rather than going round them again
+struct BeamGraph {
+    words: usize,
+    component: Vec<usize>,
+    //the tiles of component c are tiles[tile_starts[c]..tile_starts[c + 1]], and the
+    //components it leads to straight after likewise in `after`
+    tile_starts: Vec<usize>,
+    tiles: Vec<usize>,
+    after_starts: Vec<usize>,
+    after: Vec<usize>,
+    //the index into `stored` of each component's energised tiles, if it keeps them
+    stored_index: Vec<Option<usize>>,
+    stored: Vec<Vec<u64>>,
+}
+
+//what a search of the condensed graph needs, reused from one start to the next
+struct Sweep {
+    //the generation in which each component was last reached, so nothing has to be cleared
+    seen: Vec<u32>,
+    generation: u32,
+    stack: Vec<usize>,
+    bits: Vec<u64>,
+}
+
+impl BeamGraph {
+    fn new(grid: &Grid) -> Self {
+        let (component, count) = Self::tarjan(grid);
+        let words = (grid.width * grid.height).div_ceil(64);
+
+        let mut members = vec![Vec::new(); count];
+        for (state, &c) in component.iter().enumerate() {
+            members[c].push(state);
+        }
+
+        let (mut tile_starts, mut tiles) = (vec![0], Vec::new());
+        let (mut after_starts, mut after) = (vec![0], Vec::new());
+        for (c, states) in members.iter().enumerate() {
+            let mut own = states.iter().map(|state| state / 4).collect_vec();
+            own.sort_unstable();
+            own.dedup();
+            tiles.extend(own);
+            tile_starts.push(tiles.len());
+
+            let mut next = states
+                .iter()
+                .flat_map(|&state| grid.next_states(state).into_iter().flatten())
+                .map(|next| component[next])
+                .filter(|&next| next != c)
+                .collect_vec();
+            next.sort_unstable();
+            next.dedup();
+            after.extend(next);
+            after_starts.push(after.len());
+        }
+
+        let mut largest = (0..count)
+            .filter(|&c| members[c].len() > 1)
+            .collect::<Vec<_>>();
+        largest.sort_by_key(|&c| std::cmp::Reverse(members[c].len()));
+        largest.truncate(MAX_STORED);
+        //components are numbered as they're completed, after every component they lead to,
+        //so filling them in order finds the bitsets they lead to already there
+        largest.sort_unstable();
+
+        let mut graph = Self {
+            words,
+            component,
+            tile_starts,
+            tiles,
+            after_starts,
+            after,
+            stored_index: vec![None; count],
+            stored: Vec::with_capacity(largest.len()),
+        };
+        let mut sweep = graph.sweep();
+        for c in largest {
+            graph.fill(c, &mut sweep);
+            graph.stored_index[c] = Some(graph.stored.len());
+            graph.stored.push(sweep.bits.clone());
+        }
+        graph
+    }
+
+    //labels each state with its component, without recursing, so long beams can't overflow
+    //the stack
+    fn tarjan(grid: &Grid) -> (Vec<usize>, usize) {
+        let n = grid.cells.len() * 4;
+        let mut index = vec![usize::MAX; n];
+        let mut low = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut component = vec![usize::MAX; n];
+        let (mut counter, mut count) = (0, 0);
+
+        for root in 0..n {
+            if index[root] != usize::MAX {
+                continue;
+            }
+            let mut calls = vec![(root, 0)];
+            index[root] = counter;
+            low[root] = counter;
+            counter += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            while let Some((v, i)) = calls.last_mut() {
+                let v = *v;
+                if let Some(next) = grid.next_states(v).get(*i) {
+                    *i += 1;
+                    match *next {
+                        Some(w) if index[w] == usize::MAX => {
+                            index[w] = counter;
+                            low[w] = counter;
+                            counter += 1;
+                            stack.push(w);
+                            on_stack[w] = true;
+                            calls.push((w, 0));
+                        }
+                        Some(w) if on_stack[w] => low[v] = low[v].min(index[w]),
+                        _ => (),
+                    }
+                    continue;
+                }
+                calls.pop();
+                if let Some(&(u, _)) = calls.last() {
+                    low[u] = low[u].min(low[v]);
+                }
+                if low[v] == index[v] {
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component[w] = count;
+                        if w == v {
+                            break;
+                        }
+                    }
+                    count += 1;
+                }
+            }
+        }
+        (component, count)
+    }
+
+    fn sweep(&self) -> Sweep {
+        Sweep {
+            seen: vec![0; self.stored_index.len()],
+            generation: 0,
+            stack: Vec::new(),
+            bits: vec![0; self.words],
+        }
+    }
+
+    //sets the bits of every tile energised from component c, and only those
+    fn fill(&self, c: usize, sweep: &mut Sweep) {
+        sweep.bits.fill(0);
+        sweep.generation += 1;
+        sweep.seen[c] = sweep.generation;
+        sweep.stack.push(c);
+        while let Some(c) = sweep.stack.pop() {
+            if let Some(i) = self.stored_index[c] {
+                for (bit, stored) in sweep.bits.iter_mut().zip(&self.stored[i]) {
+                    *bit |= stored;
+                }
+                continue;
+            }
+            for &tile in &self.tiles[self.tile_starts[c]..self.tile_starts[c + 1]] {
+                sweep.bits[tile / 64] |= 1 << (tile % 64);
+            }
+            for &next in &self.after[self.after_starts[c]..self.after_starts[c + 1]] {
+                if sweep.seen[next] != sweep.generation {
+                    sweep.seen[next] = sweep.generation;
+                    sweep.stack.push(next);
+                }
+            }
+        }
+    }
+
+    fn energised_with(
+        &self,
+        grid: &Grid,
+        start: (usize, usize, Direction),
+        sweep: &mut Sweep,
+    ) -> usize {
+        self.fill(self.component[grid.state(start)], sweep);
+        sweep
+            .bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    //the tiles energised from each edge start, in the order of `Grid::edge_starts`
+    fn energised_from_edges(&self, grid: &Grid) -> Vec<usize> {
+        let mut sweep = self.sweep();
+        let starts = grid.edge_starts().into_iter();
+        starts
+            .map(|start| self.energised_with(grid, start, &mut sweep))
+            .collect()
+    }
+}
+
+fn parse_grid(input: &str) -> Grid {
     let width = input.lines().next().unwrap().len();
     let grid = input
         .lines()
@@ -149,21 +380,128 @@ fn main() -> anyhow::Result<()> {
             })
         })
         .collect();
-    let grid = Grid::new(grid, width);
+    Grid::new(grid, width)
+}
+
+fn main() -> anyhow::Result<()> {
+    let input = std::fs::read_to_string("inputs/day16.txt")?;
+    let grid = parse_grid(&input);
     // println!("{:?}", grid);
 
     println!("16.1: {}", energised_tiles(&grid, (0, 0, Direction::East)));
-    let mut start_positions = Vec::new();
-    start_positions.extend((0..grid.width).map(|x| (x, grid.height - 1, Direction::North)));
-    start_positions.extend((0..grid.height).map(|y| (0, y, Direction::East)));
-    start_positions.extend((0..grid.width).map(|x| (x, 0, Direction::South)));
-    start_positions.extend((0..grid.height).map(|y| (grid.width - 1, y, Direction::West)));
-
-    let part2 = start_positions
-        .into_iter()
-        .map(|start| energised_tiles(&grid, start))
-        .max();
+    let graph = BeamGraph::new(&grid);
+    let part2 = graph.energised_from_edges(&grid).into_iter().max();
     println!("16.2: {:?}", part2);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use aoc_2023::xorshift::XorShift;
+
+    use super::*;
+
+    const EXAMPLE: &str = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....
+";
+
+    fn random_grid(width: usize, height: usize, seed: u64) -> Grid {
+        let mut rng = XorShift::new(seed);
+        let cells = (0..width * height)
+            .map(|_| match rng.below(40) {
+                0 => Cell::VerticalSplitter,
+                1 => Cell::HorizontalSplitter,
+                2 => Cell::NorthEastMirror,
+                3 => Cell::SouthEastMirror,
+                _ => Cell::Empty,
+            })
+            .collect();
+        Grid::new(cells, width)
+    }
+
+    #[test]
+    fn test_energised() {
+        let grid = parse_grid(EXAMPLE);
+        let graph = BeamGraph::new(&grid);
+        let mut sweep = graph.sweep();
+        assert_eq!(energised_tiles(&grid, (0, 0, Direction::East)), 46);
+        let start = (0, 0, Direction::East);
+        assert_eq!(graph.energised_with(&grid, start, &mut sweep), 46);
+        assert_eq!(
+            graph.energised_from_edges(&grid).into_iter().max(),
+            Some(51)
+        );
+
+        for (width, height, seed) in [(10, 10, 1), (1, 7, 2), (30, 20, 3), (64, 65, 4)] {
+            let grid = random_grid(width, height, seed);
+            let graph = BeamGraph::new(&grid);
+            let starts = grid.edge_starts();
+            for (start, energised) in starts.into_iter().zip(graph.energised_from_edges(&grid)) {
+                assert_eq!(
+                    energised,
+                    energised_tiles(&grid, start),
+                    "{width}x{height} from {start:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_stored_memory() {
+        //the stored bitsets stay within a fixed number of grids' worth however big the grid
+        //gets, where one per loop or junction grew with the square of the area
+        for size in [100, 400] {
+            let grid = random_grid(size, size, 0x2023_0016);
+            let graph = BeamGraph::new(&grid);
+            let stored = graph.stored.iter().map(Vec::len).sum::<usize>();
+            assert!(stored <= MAX_STORED * graph.words);
+            assert!(!graph.stored.is_empty());
+
+            let energised = graph.energised_from_edges(&grid);
+            let starts = grid.edge_starts();
+            for i in (0..starts.len()).step_by(starts.len() / 5) {
+                assert_eq!(energised[i], energised_tiles(&grid, starts[i]));
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_edge_starts() {
+        for size in [300, 1000] {
+            let grid = random_grid(size, size, 0x2023_0016);
+            let start = std::time::Instant::now();
+            let graph = BeamGraph::new(&grid);
+            let condensed = graph.energised_from_edges(&grid).into_iter().max();
+            let stored = graph.stored.iter().map(Vec::len).sum::<usize>();
+            println!(
+                "{size}x{size}: {condensed:?} in {:?}, {} bitsets of {} components in {} KiB",
+                start.elapsed(),
+                graph.stored.len(),
+                graph.stored_index.len(),
+                stored * 8 / 1024
+            );
+        }
+
+        let grid = random_grid(300, 300, 0x2023_0016);
+        let start = std::time::Instant::now();
+        let searched = grid
+            .edge_starts()
+            .into_iter()
+            .map(|start| energised_tiles(&grid, start));
+        println!(
+            "300x300 searched: {:?} in {:?}",
+            searched.max(),
+            start.elapsed()
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use num::{BigInt, Integer};
+use aoc_2023::number_theory::lcm_all;
+use num::BigInt;
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -158,7 +159,7 @@ fn main() -> anyhow::Result<()> {
     .cloned()
     .collect();
 
-    let mut part2 = BigInt::from(1);
+    let mut periods = Vec::new();
     for goal in goals {
         let mut dependencies = HashSet::new();
         let mut new_dependencies = HashSet::new();
@@ -193,8 +194,9 @@ fn main() -> anyhow::Result<()> {
                 };
             }
         };
-        part2 = (part2).lcm(&BigInt::from(next - prev))
+        periods.push(BigInt::from(next - prev));
     }
+    let part2 = lcm_all(periods);
 
     println!("20.2: {part2}");
     Ok(())
@@ -1,14 +1,47 @@
+use std::ops::RangeInclusive;
+
 use anyhow::Context;
 use itertools::{izip, Itertools};
+use num::{integer::Roots, BigUint, Integer};
 
-fn number_of_winning_moves(total_time: u64, against: u64) -> u64 {
-    let half_time = total_time as f64 / 2.;
-    let against = against as f64;
-    let half_winning_range = (half_time.powi(2) - against).sqrt();
+//the inclusive range of hold times which travel strictly further than `record`, if any.
+//`T` must be wide enough to hold `total_time * total_time`
+fn winning_holds<T: Integer + Roots + Clone>(
+    total_time: &T,
+    record: &T,
+) -> Option<RangeInclusive<T>> {
+    let two = T::one() + T::one();
+    let four = two.clone() * two.clone();
+    let beats = |hold: &T| hold.clone() * (total_time.clone() - hold.clone()) > *record;
 
-    ((half_time + half_winning_range - 1.).ceil() - (half_time - half_winning_range + 1.).floor())
-        as u64
-        + 1
+    // hold * (total_time - hold) > record has roots at (total_time ± √(total_time² - 4·record)) / 2
+    let square = total_time.clone() * total_time.clone();
+    let four_record = four * record.clone();
+    if four_record > square {
+        return None;
+    }
+    let root = (square - four_record).sqrt();
+
+    //the integer square root puts us within one of the real lower root, so nudge into place
+    let mut lo = (total_time.clone() - root) / two.clone();
+    while lo > T::zero() && beats(&(lo.clone() - T::one())) {
+        lo = lo - T::one();
+    }
+    while !beats(&lo) {
+        lo = lo + T::one();
+        if lo.clone() * two.clone() > *total_time {
+            return None;
+        }
+    }
+
+    //distance is symmetric about total_time / 2
+    let hi = total_time.clone() - lo.clone();
+    Some(lo..=hi)
+}
+
+fn number_of_winning_moves(total_time: u64, against: u64) -> u64 {
+    winning_holds(&(total_time as u128), &(against as u128))
+        .map_or(0, |holds| (holds.end() - holds.start() + 1) as u64)
 }
 
 fn main() -> anyhow::Result<()> {
@@ -39,13 +72,16 @@ fn main() -> anyhow::Result<()> {
             line.split_whitespace()
                 .skip(1)
                 .collect::<String>()
-                .parse::<u64>()
+                .parse::<BigUint>()
                 .expect("line is a valid integer when whitespace is removed")
         })
         .collect_tuple()
         .expect("Exactly two lines");
 
-    println!("6.2: {}", number_of_winning_moves(time, victor));
+    let part2 = winning_holds(&time, &victor).map_or(BigUint::from(0u8), |holds| {
+        holds.end() - holds.start() + 1u8
+    });
+    println!("6.2: {}", part2);
     Ok(())
 }
 
@@ -60,4 +96,42 @@ mod test {
         assert_eq!(number_of_winning_moves(30, 200), 9);
         assert_eq!(number_of_winning_moves(71530, 940200), 71503);
     }
+
+    #[test]
+    fn test_winning_holds_boundaries() {
+        //holding 10 or 20 exactly equals the record, which doesn't win
+        assert_eq!(winning_holds(&30u64, &200), Some(11..=19));
+        //the best possible hold only ties the record
+        assert_eq!(winning_holds(&10u64, &25), None);
+        //only the midpoint beats it
+        assert_eq!(winning_holds(&10u64, &24), Some(5..=5));
+        assert_eq!(winning_holds(&11u64, &30), None);
+        assert_eq!(winning_holds(&11u64, &29), Some(5..=6));
+        assert_eq!(winning_holds(&5u64, &0), Some(1..=4));
+        assert_eq!(winning_holds(&0u64, &0), None);
+        assert_eq!(winning_holds(&1u64, &0), None);
+    }
+
+    #[test]
+    fn test_winning_holds_large() {
+        //well past 2^53, where f64 can no longer tell record and record + 1 apart
+        let time: u128 = 3_000_000_000_000_000_017;
+        let hold: u128 = 1_234_567_890_123_456_789;
+        let record = hold * (time - hold);
+        assert_eq!(
+            winning_holds(&time, &record),
+            Some(hold + 1..=time - hold - 1)
+        );
+        assert_eq!(
+            winning_holds(&time, &(record - 1)),
+            Some(hold..=time - hold)
+        );
+
+        let time: BigUint = "123456789012345678901234567890123".parse().unwrap();
+        let hold: BigUint = "98765432109876543210987654321".parse().unwrap();
+        let record = &hold * (&time - &hold);
+        let holds = winning_holds(&time, &record).unwrap();
+        assert_eq!(holds.start(), &(&hold + 1u8));
+        assert_eq!(holds.end(), &(&time - &hold - 1u8));
+    }
 }
@@ -1,32 +1,144 @@
-use std::{cmp::Reverse, marker::PhantomData};
+use std::cmp::Reverse;
 
+use anyhow::{bail, Context};
 use itertools::Itertools;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
-struct StandardDeck;
-#[derive(PartialEq, Eq, Clone, Copy)]
-struct JokerDeck;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TieBreak {
+    //compare card by card in the order they were dealt
+    Positional,
+    //compare the biggest groups first, as in poker
+    Grouped,
+}
 
-trait DeckType {
-    fn deck() -> [char; 13];
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RuleSet {
+    //weakest card first
+    order: Vec<char>,
+    //cards which stand in for whichever card makes the best hand
+    wildcards: Vec<char>,
+    tie_break: TieBreak,
+    hand_size: usize,
 }
 
-impl DeckType for StandardDeck {
-    fn deck() -> [char; 13] {
-        [
-            '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'J', 'Q', 'K', 'A',
-        ]
+impl RuleSet {
+    fn standard() -> Self {
+        Self {
+            order: "23456789TJQKA".chars().collect(),
+            wildcards: Vec::new(),
+            tie_break: TieBreak::Positional,
+            hand_size: 5,
+        }
+    }
+
+    fn joker() -> Self {
+        Self {
+            order: "J23456789TQKA".chars().collect(),
+            wildcards: vec!['J'],
+            ..Self::standard()
+        }
+    }
+
+    //reads `key = value` lines, with `#` comments. Only `order` is required, e.g.
+    //
+    //  order = J23456789TQKA
+    //  wildcards = J
+    //  tie_break = positional
+    //  hand_size = 5
+    fn from_config(config: &str) -> anyhow::Result<Self> {
+        let mut order = None;
+        let mut wildcards = Vec::new();
+        let mut tie_break = TieBreak::Positional;
+        let mut hand_size = 5;
+
+        for (n, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .with_context(|| format!("line {}: expected `key = value`", n + 1))?;
+            let value = value.trim();
+            match key.trim() {
+                "order" => order = Some(value.chars().collect_vec()),
+                "wildcards" => wildcards = value.chars().collect(),
+                "tie_break" => {
+                    tie_break = match value {
+                        "positional" => TieBreak::Positional,
+                        "grouped" => TieBreak::Grouped,
+                        _ => bail!("line {}: unknown tie_break `{value}`", n + 1),
+                    }
+                }
+                "hand_size" => {
+                    hand_size = value
+                        .parse()
+                        .with_context(|| format!("line {}: invalid hand_size", n + 1))?
+                }
+                key => bail!("line {}: unknown key `{key}`", n + 1),
+            }
+        }
+
+        let order = order.context("config has no `order`")?;
+        if order.is_empty() {
+            bail!("`order` needs at least one card");
+        }
+        if !order.iter().all_unique() {
+            bail!("`order` contains a card more than once");
+        }
+        if let Some(c) = wildcards.iter().find(|c| !order.contains(c)) {
+            bail!("wildcard `{c}` is not in `order`");
+        }
+        if hand_size == 0 {
+            bail!("hands must contain at least one card");
+        }
+
+        let rules = Self {
+            order,
+            wildcards,
+            tie_break,
+            hand_size,
+        };
+        let key_bits = (rules.card_bits() as usize)
+            .checked_mul(hand_size)
+            .and_then(|bits| bits.checked_add(RANK_BITS as usize));
+        if key_bits.is_none_or(|bits| bits > u128::BITS as usize) {
+            bail!("hands of {hand_size} cards are too big to rank");
+        }
+        Ok(rules)
     }
-}
 
-impl DeckType for JokerDeck {
-    fn deck() -> [char; 13] {
-        [
-            'J', '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'Q', 'K', 'A',
-        ]
+    fn strength(&self, card: char) -> Option<usize> {
+        self.order.iter().position(|&c| c == card)
+    }
+
+    fn is_wild(&self, card: char) -> bool {
+        self.wildcards.contains(&card)
+    }
+
+    //bits needed to store the strength of one card
+    fn card_bits(&self) -> u32 {
+        usize::BITS - (self.order.len() - 1).leading_zeros()
+    }
+
+    fn hand(&self, s: &str) -> Option<Hand<'_>> {
+        let cards = s.chars().collect_vec();
+        if cards.len() != self.hand_size || cards.iter().any(|&c| self.strength(c).is_none()) {
+            return None;
+        }
+        let mut hand = Hand {
+            cards,
+            rules: self,
+            key: 0,
+        };
+        hand.key = hand.packed_key();
+        Some(hand)
     }
 }
 
+//bits needed to store a `HandRank`
+const RANK_BITS: u32 = 3;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum HandRank {
     HighCard,
@@ -40,196 +152,255 @@ enum HandRank {
     FullHouse,
 
     FourOfAKind,
+    //or more, in hands bigger than five
     FiveOfAKind,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Card<DeckType> {
-    inner: char,
-    _deck_type: PhantomData<DeckType>,
-}
-
-impl<T: DeckType + std::cmp::Eq> PartialOrd for Card<T> {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl<T: DeckType + std::cmp::Eq> Ord for Card<T> {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        T::deck()
-            .iter()
-            .position(|c| c == &self.inner)
-            .cmp(&T::deck().iter().position(|c| c == &other.inner))
-    }
-}
-
-impl<T: DeckType> Card<T> {
-    fn from_char(c: char) -> Option<Self> {
-        T::deck().contains(&c).then_some(Card {
-            inner: c,
-            _deck_type: PhantomData,
+impl std::str::FromStr for HandRank {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use HandRank::*;
+        Ok(match s {
+            "HighCard" => HighCard,
+            "Pair" => Pair,
+            "TwoPair" => TwoPair,
+            "ThreeOfAKind" => ThreeOfAKind,
+            "FullHouse" => FullHouse,
+            "FourOfAKind" => FourOfAKind,
+            "FiveOfAKind" => FiveOfAKind,
+            _ => bail!("unknown hand rank `{s}`"),
         })
     }
 }
 
-impl From<Card<StandardDeck>> for Card<JokerDeck> {
-    fn from(value: Card<StandardDeck>) -> Self {
-        Card {
-            inner: value.inner,
-            _deck_type: PhantomData,
-        }
-    }
+#[derive(Debug, Clone)]
+struct Hand<'a> {
+    cards: Vec<char>,
+    rules: &'a RuleSet,
+    //rank followed by tie break strengths, so hands sort as plain integers
+    key: u128,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Hand<T>([Card<T>; 5]);
-
-impl PartialOrd for Hand<StandardDeck> {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl PartialEq for Hand<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
-impl PartialOrd for Hand<JokerDeck> {
+impl Eq for Hand<'_> {}
+
+impl PartialOrd for Hand<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Ord for Hand<StandardDeck> {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        (self.rank(), &self.0).cmp(&(other.rank(), &other.0))
-    }
-}
-
-impl Ord for Hand<JokerDeck> {
+impl Ord for Hand<'_> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        (self.rank(), &self.0).cmp(&(other.rank(), &other.0))
+        self.key.cmp(&other.key)
     }
 }
 
-impl<T> std::fmt::Display for Hand<T> {
+impl std::fmt::Display for Hand<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for card in &self.0 {
-            write!(f, "{}", card.inner)?;
+        for card in &self.cards {
+            write!(f, "{}", card)?;
         }
         Ok(())
     }
 }
 
-impl Hand<StandardDeck> {
-    fn rank(&self) -> HandRank {
-        // number of groups of size 2, 3, 4, 5
-        let mut groups = [0; 4];
-        for (_, group) in &self.0.iter().sorted().group_by(|x| *x) {
-            let size = group.count();
-            if size > 1 {
-                groups[size - 2] += 1;
-            }
+impl Hand<'_> {
+    //the card every wildcard pretends to be, if there are any wildcards
+    fn substitution(&self) -> Option<char> {
+        let rules = self.rules;
+        if !self.cards.iter().any(|&c| rules.is_wild(c)) {
+            return None;
         }
 
-        match groups {
-            [0, 0, 0, 1] => HandRank::FiveOfAKind,
-            [0, 0, 1, 0] => HandRank::FourOfAKind,
-            [1, 1, 0, 0] => HandRank::FullHouse,
-            [0, 1, 0, 0] => HandRank::ThreeOfAKind,
-            [2, 0, 0, 0] => HandRank::TwoPair,
-            [1, 0, 0, 0] => HandRank::Pair,
-            _ => HandRank::HighCard,
-        }
+        //joining the biggest group is always best, and the strongest card breaks ties
+        self.cards
+            .iter()
+            .filter(|&&c| !rules.is_wild(c))
+            .counts()
+            .into_iter()
+            .max_by_key(|&(&c, n)| (n, rules.strength(c)))
+            .map(|(&c, _)| c)
+            .or(rules.order.last().copied())
     }
-}
 
-impl Hand<JokerDeck> {
-    fn rank(&self) -> HandRank {
-        let mut jokers = 0;
-
-        let mut groups = Vec::with_capacity(5);
-        for (key, group) in &self.0.iter().sorted().group_by(|&x| x) {
-            let size = group.count();
-            if key.inner == 'J' {
-                jokers += size;
-            } else {
-                groups.push(size);
-            }
-        }
-
-        //sort group sizes high to low
-        groups.sort_unstable_by_key(|x| Reverse(*x));
-
-        //may fail if there are 5 jokers
-        if let Some(t) = groups.first_mut() {
-            *t += jokers
+    //the hand with its wildcards replaced
+    fn substituted(&self) -> Vec<char> {
+        match self.substitution() {
+            Some(s) => self
+                .cards
+                .iter()
+                .map(|&c| if self.rules.is_wild(c) { s } else { c })
+                .collect(),
+            None => self.cards.clone(),
         }
+    }
 
-        match [groups.first(), groups.get(1)] {
-            [Some(5), None] | [None, None] => HandRank::FiveOfAKind,
+    fn rank(&self) -> HandRank {
+        //group sizes high to low
+        let groups = self
+            .substituted()
+            .into_iter()
+            .counts()
+            .into_values()
+            .sorted_unstable_by_key(|&x| Reverse(x))
+            .collect_vec();
+
+        match [groups.first().copied(), groups.get(1).copied()] {
+            [Some(5..), _] => HandRank::FiveOfAKind,
             [Some(4), _] => HandRank::FourOfAKind,
-            [Some(3), Some(2)] => HandRank::FullHouse,
+            [Some(3), Some(2..)] => HandRank::FullHouse,
             [Some(3), _] => HandRank::ThreeOfAKind,
             [Some(2), Some(2)] => HandRank::TwoPair,
             [Some(2), _] => HandRank::Pair,
             _ => HandRank::HighCard,
         }
     }
-}
 
-impl<T: DeckType + std::cmp::Eq> Hand<T> {
-    fn new(cards: [Card<T>; 5]) -> Self {
-        Self(cards)
+    fn packed_key(&self) -> u128 {
+        let bits = self.rules.card_bits();
+        self.tie_break_key()
+            .into_iter()
+            .fold(self.rank() as u128, |key, s| (key << bits) | s as u128)
     }
-    fn from_str(s: &str) -> Option<Self> {
-        let t = s
-            .chars()
-            .take(5)
-            .map(Card::<T>::from_char)
-            .collect::<Option<Vec<_>>>()?;
-
-        //try_into should never fail here since we've already got a vec
-        //of size 5, with valid cards in it
-        Some(Self::new(t.try_into().ok()?))
+
+    //card strengths, in the order they should be compared
+    fn tie_break_key(&self) -> Vec<usize> {
+        let rules = self.rules;
+        let strength = |c: char| {
+            rules
+                .strength(c)
+                .expect("hands only hold cards in the deck")
+        };
+        match rules.tie_break {
+            TieBreak::Positional => self.cards.iter().map(|&c| strength(c)).collect(),
+            TieBreak::Grouped => self
+                .substituted()
+                .into_iter()
+                .counts()
+                .into_iter()
+                .map(|(c, n)| (n, strength(c)))
+                .sorted_unstable_by_key(|&group| Reverse(group))
+                .flat_map(|(n, s)| std::iter::repeat_n(s, n))
+                .collect(),
+        }
     }
 }
 
-impl From<Hand<StandardDeck>> for Hand<JokerDeck> {
-    fn from(value: Hand<StandardDeck>) -> Self {
-        Hand(value.0.map(From::from))
-    }
+fn parse_hands<'a>(input: &str, rules: &'a RuleSet) -> anyhow::Result<Vec<(Hand<'a>, u32)>> {
+    input
+        .lines()
+        .map(|line| {
+            let (cards, bid) = line
+                .split_once(' ')
+                .context("Line consists of hand and bid")?;
+            Ok((
+                rules
+                    .hand(cards)
+                    .with_context(|| format!("`{cards}` is not a valid hand"))?,
+                bid.parse::<u32>().context("Bid is integer")?,
+            ))
+        })
+        .collect()
 }
 
-fn total_bids<T>(hands: &[(Hand<T>, u32)]) -> u32 {
+fn total_bids(hands: &[(Hand, u32)]) -> u32 {
     hands
         .iter()
         .enumerate()
         .map(|(i, (_, bid))| (i + 1) as u32 * (*bid))
         .sum::<u32>()
 }
-fn main() -> anyhow::Result<()> {
-    let input = std::fs::read_to_string("inputs/day7.txt")?;
-
-    let mut hands: Vec<(Hand<StandardDeck>, u32)> = input
-        .lines()
-        .map(|line| {
-            let (cards, bid) = line.split_once(' ').expect("Line consists of hand and bid");
-            (
-                Hand::from_str(cards).expect("Hand is valid hand"),
-                bid.parse::<u32>().expect("Bid is integer"),
-            )
-        })
-        .collect_vec();
 
+//hands and their bids, weakest first
+fn ranked_hands<'a>(input: &str, rules: &'a RuleSet) -> anyhow::Result<Vec<(Hand<'a>, u32)>> {
+    let mut hands = parse_hands(input, rules)?;
     hands.sort();
-    println!("7.1: {}", total_bids(&hands));
+    Ok(hands)
+}
+
+//one line per ranked hand, weakest first, optionally only those of rank `only`
+fn report(hands: &[(Hand, u32)], only: Option<HandRank>) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:>6}  {:<hand$}  {:<12}  {:<4}  {:>5}  {:>10}",
+        "pos",
+        "hand",
+        "rank",
+        "wild",
+        "bid",
+        "winnings",
+        hand = hands.first().map_or(4, |(h, _)| h.cards.len().max(4))
+    )
+    .unwrap();
+    for (i, (hand, bid)) in hands.iter().enumerate() {
+        let rank = hand.rank();
+        if only.is_some_and(|only| only != rank) {
+            continue;
+        }
+        writeln!(
+            out,
+            "{:>6}  {:<hand$}  {:<12}  {:<4}  {:>5}  {:>10}",
+            i + 1,
+            hand.to_string(),
+            format!("{rank:?}"),
+            hand.substitution()
+                .map_or("-".to_string(), |c| format!("->{c}")),
+            bid,
+            (i + 1) as u32 * bid,
+            hand = hand.cards.len().max(4)
+        )
+        .unwrap();
+    }
+    out
+}
 
-    let mut hands: Vec<(Hand<JokerDeck>, u32)> = hands
-        .into_iter()
-        .map(|(hand, bid)| (hand.into(), bid))
-        .collect();
+fn main() -> anyhow::Result<()> {
+    let input = std::fs::read_to_string("inputs/day7.txt")?;
 
-    hands.sort();
+    //`--report` lists every ranked hand, `--rank FullHouse` narrows it to one rank,
+    //and any other arguments are rule set config files to play the same hands under
+    let mut show_report = false;
+    let mut only = None;
+    let mut configs = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--report" => show_report = true,
+            "--rank" => {
+                show_report = true;
+                only = Some(args.next().context("--rank needs a hand rank")?.parse()?);
+            }
+            _ => configs.push(arg),
+        }
+    }
 
-    println!("7.2: {}", total_bids(&hands));
+    let mut rule_sets = vec![
+        ("7.1".to_string(), RuleSet::standard()),
+        ("7.2".to_string(), RuleSet::joker()),
+    ];
+    for path in configs {
+        let rules = RuleSet::from_config(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("Invalid rule set {path}"))?;
+        rule_sets.push((path, rules));
+    }
+
+    for (name, rules) in &rule_sets {
+        let hands = ranked_hands(&input, rules)?;
+        if show_report {
+            print!("{}", report(&hands, only));
+        }
+        println!("{name}: {}", total_bids(&hands));
+    }
 
     Ok(())
 }
@@ -238,31 +409,188 @@ fn main() -> anyhow::Result<()> {
 mod test {
     use super::*;
 
+    const EXAMPLE: &str = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
     #[test]
     fn test_hand_cmp() {
-        let hand1 = Hand::<StandardDeck>::from_str("32T3K").unwrap();
-        let hand2 = Hand::<StandardDeck>::from_str("KK677").unwrap();
+        let standard = RuleSet::standard();
+        let hand1 = standard.hand("32T3K").unwrap();
+        let hand2 = standard.hand("KK677").unwrap();
         assert_eq!(hand1.rank(), HandRank::Pair);
         assert_eq!(hand2.rank(), HandRank::TwoPair);
         assert_eq!(hand1.rank().cmp(&hand2.rank()), std::cmp::Ordering::Less);
         assert_eq!(hand1.cmp(&hand2), std::cmp::Ordering::Less);
 
-        let hand3 = Hand::<StandardDeck>::from_str("33332").unwrap();
-        let hand4 = Hand::<StandardDeck>::from_str("2AAAA").unwrap();
+        let hand3 = standard.hand("33332").unwrap();
+        let hand4 = standard.hand("2AAAA").unwrap();
         assert_eq!(hand3.cmp(&hand4), std::cmp::Ordering::Greater);
 
         assert_eq!(
-            Hand::<StandardDeck>::from_str("77888")
+            standard
+                .hand("77888")
                 .unwrap()
-                .cmp(&Hand::from_str("77788").unwrap()),
+                .cmp(&standard.hand("77788").unwrap()),
             std::cmp::Ordering::Greater
         );
 
         assert_eq!(
-            Hand::<StandardDeck>::from_str("77888")
+            standard
+                .hand("77888")
                 .unwrap()
-                .cmp(&Hand::from_str("77788").unwrap()),
+                .cmp(&standard.hand("77788").unwrap()),
             std::cmp::Ordering::Greater
         );
     }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(
+            total_bids(&ranked_hands(EXAMPLE, &RuleSet::standard()).unwrap()),
+            6440
+        );
+        assert_eq!(
+            total_bids(&ranked_hands(EXAMPLE, &RuleSet::joker()).unwrap()),
+            5905
+        );
+
+        let joker = RuleSet::joker();
+        assert_eq!(joker.hand("JJJJJ").unwrap().rank(), HandRank::FiveOfAKind);
+        assert_eq!(joker.hand("KTJJT").unwrap().rank(), HandRank::FourOfAKind);
+        assert_eq!(joker.hand("KTJJT").unwrap().substitution(), Some('T'));
+        assert!(joker.hand("JKKK2").unwrap() < joker.hand("QQQQ2").unwrap());
+    }
+
+    #[test]
+    fn test_report() {
+        let rules = RuleSet::joker();
+        let hands = ranked_hands(EXAMPLE, &rules).unwrap();
+
+        let full = report(&hands, None);
+        assert_eq!(full.lines().count(), 6);
+        assert_eq!(
+            full.lines()
+                .nth(1)
+                .unwrap()
+                .split_whitespace()
+                .collect_vec(),
+            ["1", "32T3K", "Pair", "-", "765", "765"]
+        );
+        assert_eq!(
+            full.lines()
+                .last()
+                .unwrap()
+                .split_whitespace()
+                .collect_vec(),
+            ["5", "KTJJT", "FourOfAKind", "->T", "220", "1100"]
+        );
+
+        let fours = report(&hands, Some("FourOfAKind".parse().unwrap()));
+        assert_eq!(fours.lines().count(), 4);
+        assert!(report(&hands, Some(HandRank::FullHouse))
+            .lines()
+            .nth(1)
+            .is_none());
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let rules = RuleSet::from_config(
+            "# two kinds of wildcard, poker tie breaks and three card hands
+            order = 2345678TJQKA9
+            wildcards = 29
+            tie_break = grouped
+            hand_size = 3",
+        )
+        .unwrap();
+        assert_eq!(rules.hand("2K9").unwrap().rank(), HandRank::ThreeOfAKind);
+        assert_eq!(rules.hand("3K4").unwrap().rank(), HandRank::HighCard);
+        assert!(rules.hand("3K45").is_none());
+        //grouped: the pair of 3s is compared before the kicker
+        assert!(rules.hand("A33").unwrap() < rules.hand("K44").unwrap());
+        assert!(rules.hand("A44").unwrap() > rules.hand("K44").unwrap());
+
+        assert!(RuleSet::from_config("wildcards = J").is_err());
+        assert!(RuleSet::from_config("order = AKA").is_err());
+        assert!(RuleSet::from_config("order = AK\nwildcards = J").is_err());
+        assert!(RuleSet::from_config("order = AK\ntie_break = suits").is_err());
+        assert!(RuleSet::from_config("order =").is_err());
+
+        //31 four-bit cards fit alongside the rank, 32 don't, and sizes which would wrap
+        //round when multiplied or truncated are refused rather than overflowing
+        let sized =
+            |n: usize| RuleSet::from_config(&format!("order = 23456789TJQKA\nhand_size = {n}"));
+        assert!(sized(31).is_ok());
+        assert!(sized(32).is_err());
+        assert!(sized(1 << 32).is_err());
+        assert!(sized(usize::MAX).is_err());
+        assert!(sized(usize::MAX / 4 + 1).is_err());
+    }
+}
+
+#[cfg(test)]
+mod bench {
+    use std::time::Instant;
+
+    use aoc_2023::xorshift::XorShift;
+
+    use super::*;
+
+    fn deal(rules: &RuleSet, n: usize, seed: u64) -> Vec<String> {
+        let mut rng = XorShift::new(seed);
+        (0..n)
+            .map(|_| {
+                (0..rules.hand_size)
+                    .map(|_| rules.order[rng.below(rules.order.len())])
+                    .collect()
+            })
+            .collect()
+    }
+
+    //how hands were compared before they carried a packed key
+    fn unpacked_cmp(a: &Hand, b: &Hand) -> std::cmp::Ordering {
+        (a.rank(), a.tie_break_key()).cmp(&(b.rank(), b.tie_break_key()))
+    }
+
+    #[test]
+    fn test_packed_key_matches_unpacked() {
+        let grouped = RuleSet {
+            tie_break: TieBreak::Grouped,
+            ..RuleSet::joker()
+        };
+        for rules in [RuleSet::standard(), RuleSet::joker(), grouped] {
+            let dealt = deal(&rules, 2000, 0x2023_0007);
+            let hands = dealt.iter().map(|s| rules.hand(s).unwrap()).collect_vec();
+            for (a, b) in hands.iter().tuple_windows() {
+                assert_eq!(a.cmp(b), unpacked_cmp(a, b), "{a} vs {b}");
+            }
+        }
+    }
+
+    //cargo test --release --bin day7 -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_million_hands() {
+        let rules = RuleSet::joker();
+        let dealt = deal(&rules, 1_000_000, 0x2023_0007);
+        let hands = dealt.iter().map(|s| rules.hand(s).unwrap()).collect_vec();
+
+        let mut unpacked = hands.clone();
+        let start = Instant::now();
+        unpacked.sort_by(unpacked_cmp);
+        println!("unpacked: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let mut packed = dealt
+            .iter()
+            .map(|s| rules.hand(s).unwrap().key)
+            .collect_vec();
+        packed.sort_unstable();
+        println!("packed (including encoding): {:?}", start.elapsed());
+
+        assert!(unpacked.iter().map(|h| h.key).eq(packed));
+    }
 }
@@ -1,131 +1,381 @@
-use once_cell::sync::Lazy;
 use std::collections::{
     hash_map::Entry::{Occupied, Vacant},
-    HashMap,
+    HashMap, HashSet,
 };
 
-use anyhow::Context;
+use anyhow::{bail, Context};
+use aoc_2023::number_theory::crt_pair;
 use itertools::Itertools;
-use num::{traits::Euclid, BigInt, Integer};
+use num::{BigInt, Integer, One, Zero};
 
-static ZERO: Lazy<BigInt> = Lazy::new(|| BigInt::from(0));
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    Left,
+    Right,
+}
 
-//returns start and end point of first repeat in the instructions, with visits to final nodes
-fn find_repeat_and_z_visits(
-    starting: &str,
-    instructions: &str,
-    map: &HashMap<&str, (&str, &str)>,
-) -> (usize, usize, Vec<usize>) {
-    let cycle_length = instructions.len();
-    let mut location = starting;
+#[derive(Debug, Clone)]
+struct Network<'a> {
+    instructions: Vec<Instruction>,
+    names: Vec<&'a str>,
+    //left and right neighbours of each node, by index
+    edges: Vec<(usize, usize)>,
+    index: HashMap<&'a str, usize>,
+}
 
-    let mut visited_at = HashMap::new();
-    let mut final_visited = Vec::new();
+impl<'a> Network<'a> {
+    fn parse(input: &'a str) -> anyhow::Result<Self> {
+        let mut lines = input.lines().enumerate();
+        let (_, instructions) = lines.next().context("No lines")?;
+        let instructions = instructions
+            .trim()
+            .chars()
+            .enumerate()
+            .map(|(i, c)| match c {
+                'L' => Ok(Instruction::Left),
+                'R' => Ok(Instruction::Right),
+                _ => bail!("invalid instruction {c:?} at position {}", i + 1),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if instructions.is_empty() {
+            bail!("no instructions");
+        }
 
-    for (i, instruction) in instructions.chars().cycle().enumerate() {
-        if location.ends_with('Z') {
-            final_visited.push(i);
+        let mut nodes = Vec::new();
+        for (n, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+            let node = line
+                .split_once('=')
+                .and_then(|(name, targets)| {
+                    let (left, right) = targets
+                        .trim()
+                        .strip_prefix('(')?
+                        .strip_suffix(')')?
+                        .split_once(',')?;
+                    Some((name.trim(), left.trim(), right.trim()))
+                })
+                .filter(|(name, left, right)| ![name, left, right].iter().any(|s| s.is_empty()))
+                .with_context(|| format!("line {}: expected `NAME = (LEFT, RIGHT)`", n + 1))?;
+            nodes.push((n, node));
         }
-        match visited_at.entry((i % cycle_length, location)) {
-            Occupied(repeat_start) => return (*repeat_start.get(), i, final_visited),
-            Vacant(e) => {
-                e.insert(i);
+
+        let mut index = HashMap::new();
+        for (i, (n, (name, _, _))) in nodes.iter().enumerate() {
+            if index.insert(*name, i).is_some() {
+                bail!("line {}: node {name} is defined twice", n + 1);
             }
         }
+
+        let edges = nodes
+            .iter()
+            .map(|(n, (name, left, right))| {
+                let lookup = |target: &str| {
+                    index.get(target).copied().with_context(|| {
+                        format!("line {}: node {name} leads to missing node {target}", n + 1)
+                    })
+                };
+                Ok((lookup(left)?, lookup(right)?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            instructions,
+            names: nodes.into_iter().map(|(_, (name, _, _))| name).collect(),
+            edges,
+            index,
+        })
+    }
+
+    fn node(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+
+    fn nodes_where(&self, predicate: impl Fn(&str) -> bool) -> Vec<usize> {
+        (0..self.names.len())
+            .filter(|&i| predicate(self.names[i]))
+            .collect()
+    }
+
+    fn step(&self, node: usize, instruction: Instruction) -> usize {
         match instruction {
-            'L' => location = map[location].0,
-            'R' => location = map[location].1,
-            _ => unreachable!(),
-        };
+            Instruction::Left => self.edges[node].0,
+            Instruction::Right => self.edges[node].1,
+        }
     }
-    unreachable!()
-}
 
-//gcd, u, v
-fn bézout(m: BigInt, n: BigInt) -> (BigInt, BigInt, BigInt) {
-    if n > m {
-        let (g, v, u) = bézout(n, m);
-        return (g, u, v);
+    //number of steps before first arriving at a goal, or None if the walk loops without one
+    fn steps_until(&self, start: usize, goal: impl Fn(&str) -> bool) -> Option<usize> {
+        let mut seen = HashSet::new();
+        let mut location = start;
+        for (i, &instruction) in self.instructions.iter().cycle().enumerate() {
+            if i > 0 && goal(self.names[location]) {
+                return Some(i);
+            }
+            if !seen.insert((i % self.instructions.len(), location)) {
+                return None;
+            }
+            location = self.step(location, instruction);
+        }
+        unreachable!()
+    }
+
+    //walks from `start` until the walk repeats, noting every visit to a goal on the way
+    fn cycle_info(&self, start: usize, goal: impl Fn(&str) -> bool) -> CycleInfo {
+        let mut location = start;
+
+        let mut visited_at = HashMap::new();
+        let mut goal_visits = Vec::new();
+
+        for (i, &instruction) in self.instructions.iter().cycle().enumerate() {
+            match visited_at.entry((i % self.instructions.len(), location)) {
+                Occupied(repeat_start) => {
+                    let cycle_start = *repeat_start.get();
+                    let split = goal_visits.partition_point(|&visit| visit < cycle_start);
+                    let cycle_hits = goal_visits.split_off(split);
+                    return CycleInfo {
+                        prefix_hits: goal_visits,
+                        cycle_start,
+                        cycle_len: i - cycle_start,
+                        cycle_hits,
+                    };
+                }
+                Vacant(e) => {
+                    e.insert(i);
+                }
+            }
+            if goal(self.names[location]) {
+                goal_visits.push(i);
+            }
+            location = self.step(location, instruction);
+        }
+        unreachable!()
     }
-    let (q, r) = m.div_rem(&n);
+}
+
+//when a walker is at a goal: at each of `prefix_hits`, which all come before `cycle_start`,
+//and at `h + k * cycle_len` for every `h` in `cycle_hits` and k >= 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CycleInfo {
+    prefix_hits: Vec<usize>,
+    cycle_start: usize,
+    cycle_len: usize,
+    cycle_hits: Vec<usize>,
+}
 
-    if r == *ZERO {
-        return (n.clone(), BigInt::from(1), (q - 1) * n);
+impl CycleInfo {
+    fn at_goal(&self, step: usize) -> bool {
+        if step < self.cycle_start {
+            return self.prefix_hits.contains(&step);
+        }
+        self.cycle_hits
+            .iter()
+            .any(|h| h % self.cycle_len == step % self.cycle_len)
     }
+}
 
-    //found the GCD case
-    if m.rem_euclid(&r) == *ZERO && n.rem_euclid(&r) == *ZERO {
-        return (r, BigInt::from(1), -q);
+//the first step (after setting off) at which every walker is at a goal at once
+fn first_simultaneous_goal(walkers: &[CycleInfo]) -> Option<BigInt> {
+    //while any walker is still before its cycle, it has to be at one of its prefix hits
+    let before_cycles = walkers
+        .iter()
+        .flat_map(|w| &w.prefix_hits)
+        .filter(|&&step| step > 0)
+        .sorted_unstable()
+        .find(|&&step| walkers.iter().all(|w| w.at_goal(step)));
+    if let Some(&step) = before_cycles {
+        return Some(BigInt::from(step));
     }
-    // g = n*u + r*v
-    // m = n*q + r
-    // m * v - g = n*q*v - n*u
-    let (g, u, v) = bézout(n, r);
-    (g, v.clone(), u - q * v)
+
+    //otherwise every walker is in its cycle, so the step satisfies one residue per walker
+    let earliest = BigInt::from(walkers.iter().map(|w| w.cycle_start).max()?.max(1));
+    let residues = walkers.iter().map(|w| {
+        let n = BigInt::from(w.cycle_len);
+        w.cycle_hits
+            .iter()
+            .map(|&h| (BigInt::from(h) % &n, n.clone()))
+            .collect_vec()
+    });
+    residues
+        .fold(vec![(BigInt::zero(), BigInt::one())], |xs, ys| {
+            xs.into_iter()
+                .cartesian_product(ys)
+                .filter_map(|(x, y)| crt_pair(x, y))
+                .collect()
+        })
+        .into_iter()
+        .map(|(x, n)| {
+            //the first step congruent to x which is no earlier than `earliest`
+            if x >= earliest {
+                x
+            } else {
+                let behind = &earliest - &x;
+                x + behind.div_ceil(&n) * n
+            }
+        })
+        .min()
 }
+
 fn main() -> anyhow::Result<()> {
     let input = std::fs::read_to_string("inputs/day8.txt")?;
 
-    let mut input = input.lines();
-    let instructions = input.next().context("No lines")?;
-    input.next();
-    let map: HashMap<_, _> = input
-        .map(|line| (&line[0..3], (&line[7..10], &line[12..15])))
-        .collect();
-    let mut current = "AAA";
-    let part1 = instructions
-        .chars()
-        .cycle()
-        .position(|instruction| {
-            match instruction {
-                'L' => current = map[current].0,
-                'R' => current = map[current].1,
-                _ => unreachable!(),
-            };
-            current == "ZZZ"
-        })
-        .unwrap()
-        + 1;
+    let network = Network::parse(&input)?;
+
+    let start = network.node("AAA").context("no AAA node")?;
+    let part1 = network
+        .steps_until(start, |name| name == "ZZZ")
+        .context("ZZZ is never reached")?;
 
     println!("8.1 {part1}");
 
-    let current = map.keys().filter(|k: &&&str| k.ends_with('A'));
+    let starts = network.nodes_where(|name| name.ends_with('A'));
 
-    let repeats = current.map(|x| {
-        let (start, repeat, goals) = find_repeat_and_z_visits(x, instructions, &map);
-        (
-            BigInt::from(repeat - start),
-            goals
-                .into_iter()
-                .map(|x| BigInt::from(x) % (repeat - start))
-                .collect::<Vec<_>>(),
-        )
-    });
+    let walkers = starts
+        .into_iter()
+        .map(|x| network.cycle_info(x, |name| name.ends_with('Z')))
+        .collect_vec();
 
-    let (n, solutions) = repeats
-        .reduce(|(n, xs), (m, ys)| {
-            let (g, u, v) = bézout(n.clone(), m.clone());
-            let lcm = n.clone() * m.clone() / g.clone();
-            (
-                lcm.clone(),
-                xs.into_iter()
-                    .cartesian_product(ys)
-                    .map(|(a, b)| {
-                        ((a * v.clone() * n.clone() + b * u.clone() * m.clone()) / g.clone())
-                            .rem_euclid(&lcm)
-                    })
-                    .collect::<Vec<_>>(),
-            )
-        })
+    let part2 = first_simultaneous_goal(&walkers)
+        .context("the walkers never reach their goals together")?;
+
+    println!("8.2 {part2}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use aoc_2023::xorshift::XorShift;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let network = Network::parse(
+            "LLR
+
+ALPHA = (BEE, BEE)
+BEE = (ALPHA, ZZZ)
+ZZZ = (ZZZ, ZZZ)",
+        )
         .unwrap();
+        let start = network.node("ALPHA").unwrap();
+        assert_eq!(network.steps_until(start, |name| name == "ZZZ"), Some(6));
+        assert_eq!(network.nodes_where(|name| name.len() > 3), vec![start]);
 
-    let part2 = solutions
-        .into_iter()
-        .map(|x| if x == *ZERO { n.clone() } else { x })
-        .min()
+        let err = Network::parse("LXR\n\nAAA = (AAA, AAA)").unwrap_err();
+        assert_eq!(err.to_string(), "invalid instruction 'X' at position 2");
+
+        let err = Network::parse("LR\n\nAAA = (AAA, BBB)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3: node AAA leads to missing node BBB"
+        );
+
+        assert!(Network::parse("LR\n\nAAA = AAA, BBB").is_err());
+        assert!(Network::parse("LR\n\nAAA = (AAA, AAA)\nAAA = (AAA, AAA)").is_err());
+    }
+
+    #[test]
+    fn test_unreachable_goal() {
+        let network =
+            Network::parse("LR\n\nAAA = (BBB, BBB)\nBBB = (AAA, AAA)\nZZZ = (ZZZ, ZZZ)").unwrap();
+        assert_eq!(network.steps_until(0, |name| name == "ZZZ"), None);
+    }
+
+    //a walk from each start to the first simultaneous goal, one step at a time
+    fn brute_force(network: &Network, starts: &[usize], limit: usize) -> Option<usize> {
+        let mut locations = starts.to_vec();
+        for (i, &instruction) in network.instructions.iter().cycle().enumerate().take(limit) {
+            if i > 0 && locations.iter().all(|&l| network.names[l].starts_with('Z')) {
+                return Some(i);
+            }
+            for l in &mut locations {
+                *l = network.step(*l, instruction);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_cycle_info() {
+        //ZZZ is only visited on the way into the loop, BBB <-> ZB is visited every other step
+        let network = Network::parse(
+            "L
+
+AAA = (ZZZ, ZZZ)
+ZZZ = (BBB, BBB)
+BBB = (ZB, ZB)
+ZB = (BBB, BBB)",
+        )
         .unwrap();
+        let info = network.cycle_info(0, |name| name.starts_with('Z'));
+        assert_eq!(
+            info,
+            CycleInfo {
+                prefix_hits: vec![1],
+                cycle_start: 2,
+                cycle_len: 2,
+                cycle_hits: vec![3],
+            }
+        );
+        assert!(info.at_goal(1) && info.at_goal(5) && !info.at_goal(2) && !info.at_goal(0));
 
-    println!("8.2 {part2}");
+        //a walker which is only ever at a goal at step 1 can meet another there
+        let other = CycleInfo {
+            prefix_hits: vec![],
+            cycle_start: 0,
+            cycle_len: 1,
+            cycle_hits: vec![0],
+        };
+        assert_eq!(
+            first_simultaneous_goal(&[info.clone(), other]),
+            Some(BigInt::from(1))
+        );
+        let other = CycleInfo {
+            prefix_hits: vec![],
+            cycle_start: 0,
+            cycle_len: 4,
+            cycle_hits: vec![0, 2],
+        };
+        assert_eq!(first_simultaneous_goal(&[info, other]), None);
+    }
 
-    Ok(())
+    #[test]
+    fn test_against_brute_force() {
+        let mut rng = XorShift::new(0x2023_0008);
+        let mut random = |n: usize| rng.below(n);
+
+        for _ in 0..500 {
+            let n_nodes = 2 + random(7);
+            let names = (0..n_nodes)
+                .map(|i| format!("{}{i}", if random(3) == 0 { 'Z' } else { 'N' }))
+                .collect_vec();
+            let mut input: String = (0..1 + random(4))
+                .map(|_| if random(2) == 0 { 'L' } else { 'R' })
+                .collect();
+            input.push_str("\n\n");
+            for name in &names {
+                input.push_str(&format!(
+                    "{name} = ({}, {})\n",
+                    names[random(n_nodes)],
+                    names[random(n_nodes)]
+                ));
+            }
+            let network = Network::parse(&input).unwrap();
+
+            let starts = (0..1 + random(3)).map(|_| random(n_nodes)).collect_vec();
+            let walkers = starts
+                .iter()
+                .map(|&s| network.cycle_info(s, |name| name.starts_with('Z')))
+                .collect_vec();
+
+            //cycles are at most 8 nodes * 4 instructions long, so every combination of
+            //up to 3 walkers has come round by 32^3 steps past the longest prefix
+            let limit = 40_000;
+            assert_eq!(
+                first_simultaneous_goal(&walkers),
+                brute_force(&network, &starts, limit).map(BigInt::from),
+                "{input} from {starts:?}"
+            );
+        }
+    }
 }
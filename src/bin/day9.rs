@@ -1,36 +1,329 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Context};
 use itertools::Itertools;
+use num::{rational::Ratio, BigInt, Integer, Signed};
+
+//each row of the triangle is the differences of the row above
+fn difference_rows<T: Integer + Signed + Clone>(values: &[T]) -> Vec<Vec<T>> {
+    let mut rows = vec![values.to_vec()];
+    while let Some(row) = rows
+        .last()
+        .filter(|row| row.len() > 1 && !row.iter().all(T::is_zero))
+    {
+        let next = row
+            .iter()
+            .tuple_windows()
+            .map(|(a, b)| b.clone() - a.clone())
+            .collect();
+        rows.push(next);
+    }
+    rows
+}
+
+//an OASIS history, stored in Newton forward-difference form
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Sequence<T> {
+    //Δ^k of the first reading, for k up to the degree
+    differences: Vec<T>,
+    len: usize,
+}
 
-fn get_next(seq: &[i64]) -> i64 {
-    if seq.iter().all_equal() {
-        return seq.first().copied().unwrap_or(0);
+impl<T: Integer + Signed + Clone + From<i64>> Sequence<T> {
+    //fails unless the readings are enough to pin down a polynomial: at least two of them,
+    //with some row of differences holding at least two equal values
+    fn fit(values: &[T]) -> anyhow::Result<Self> {
+        if values.len() < 2 {
+            bail!("too few values to extrapolate: {}", values.len());
+        }
+
+        let mut differences = Vec::new();
+        let mut row = values.to_vec();
+        while let Some(first) = row.first() {
+            differences.push(first.clone());
+            if row.iter().all_equal() {
+                break;
+            }
+            row = row
+                .into_iter()
+                .tuple_windows()
+                .map(|(a, b)| b - a)
+                .collect();
+        }
+        if row.len() < 2 {
+            bail!(
+                "not a polynomial of degree below {}: the differences never settle",
+                values.len() - 1
+            );
+        }
+
+        Ok(Self {
+            differences,
+            len: values.len(),
+        })
     }
-    let diffs: Vec<_> = seq.iter().tuple_windows().map(|(a, b)| b - a).collect();
 
-    *seq.last().unwrap_or(&0) + get_next(&diffs)
+    fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
+    }
+
+    //the value at index x of the readings, so 0 is the first reading and -1 the one before it
+    fn at(&self, x: i64) -> T {
+        let x = T::from(x);
+        let mut binomial = T::one();
+        let mut total = T::zero();
+        for (k, d) in self.differences.iter().enumerate() {
+            total = total + d.clone() * binomial.clone();
+            //C(x, k + 1) = C(x, k) * (x - k) / (k + 1), which always divides exactly
+            let k = T::from(k as i64);
+            binomial = binomial * (x.clone() - k.clone()) / (k + T::one());
+        }
+        total
+    }
+
+    //the value `k` steps after the last reading
+    fn next(&self, k: usize) -> T {
+        self.at((self.len - 1 + k) as i64)
+    }
+
+    //the value `k` steps before the first reading
+    fn previous(&self, k: usize) -> T {
+        self.at(-(k as i64))
+    }
+
+    //c such that the reading at index x is c[0] + c[1]*x + c[2]*x^2 + ...
+    fn coefficients(&self) -> Vec<Ratio<T>> {
+        let mut coefficients = vec![Ratio::from_integer(T::zero()); self.differences.len()];
+        //x(x-1)...(x-k+1), lowest power first
+        let mut falling = vec![T::one()];
+        let mut factorial = T::one();
+        for (k, d) in self.differences.iter().enumerate() {
+            for (c, f) in coefficients.iter_mut().zip(&falling) {
+                *c = c.clone() + Ratio::new(d.clone() * f.clone(), factorial.clone());
+            }
+            let k = T::from(k as i64);
+            let mut next = vec![T::zero(); falling.len() + 1];
+            for (i, f) in falling.iter().enumerate() {
+                next[i + 1] = next[i + 1].clone() + f.clone();
+                next[i] = next[i].clone() - f.clone() * k.clone();
+            }
+            falling = next;
+            factorial = factorial * (k + T::one());
+        }
+        coefficients
+    }
+}
+
+//the difference triangle laid out as in the puzzle, with each row extrapolated one step
+//when the readings allow it
+fn difference_triangle<T: Integer + Signed + Clone + Display>(values: &[T]) -> String {
+    let mut rows = difference_rows(values);
+    let settled = rows
+        .last()
+        .is_some_and(|row| row.len() > 1 || (rows.len() > 1 && row.iter().all(T::is_zero)));
+    if settled {
+        let mut below = T::zero();
+        for row in rows.iter_mut().rev() {
+            below = row.last().cloned().unwrap_or_else(T::zero) + below;
+            row.push(below.clone());
+        }
+    }
+
+    let width = rows
+        .iter()
+        .flatten()
+        .map(|x| x.to_string().len())
+        .max()
+        .unwrap_or(1);
+    //an even pitch so each row sits exactly between the numbers above it
+    let pitch = (width + 2).next_multiple_of(2);
+    let lines = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let indent = " ".repeat(i * pitch / 2);
+            let cells: String = row.iter().map(|x| format!("{x:>pitch$}")).collect();
+            format!("{indent}{cells}")
+        })
+        .collect_vec();
+    let margin = lines
+        .iter()
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| format!("{}\n", &line[margin..]))
+        .collect()
+}
+
+fn parse_line<T: FromStr>(line: &str) -> anyhow::Result<Vec<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    Ok(line
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<T>, _>>()?)
 }
 
 fn main() -> anyhow::Result<()> {
     let input = std::fs::read_to_string("inputs/day9.txt")?;
-    let mut input: Vec<Vec<i64>> = input
+
+    //`--check` prints every line's difference triangle and whether it can be extrapolated
+    if std::env::args().any(|arg| arg == "--check") {
+        for (n, line) in input.lines().enumerate() {
+            let values = parse_line::<i64>(line).with_context(|| format!("line {}", n + 1))?;
+            match Sequence::fit(&values) {
+                Ok(sequence) => println!("line {}: degree {}", n + 1, sequence.degree()),
+                Err(e) => println!("line {}: {e}", n + 1),
+            }
+            println!("{}", difference_triangle(&values));
+        }
+        return Ok(());
+    }
+
+    let sequences: Vec<Sequence<i64>> = input
         .lines()
-        .map(|line| {
-            line.split_whitespace()
-                .map(str::parse)
-                .collect::<Result<Vec<i64>, _>>()
-                .unwrap()
+        .enumerate()
+        .map(|(n, line)| {
+            Sequence::fit(&parse_line(line)?).with_context(|| format!("line {}", n + 1))
         })
-        .collect();
-    let part1 = input.iter().map(|line| get_next(line)).sum::<i64>();
+        .collect::<anyhow::Result<_>>()?;
+    let part1 = sequences.iter().map(|s| s.next(1)).sum::<i64>();
     println!("{}", part1);
 
-    let part2 = input
-        .iter_mut()
-        .map(|line| {
-            line.reverse();
-            get_next(line)
-        })
-        .sum::<i64>();
+    let part2 = sequences.iter().map(|s| s.previous(1)).sum::<i64>();
 
     println!("{}", part2);
+
+    //`--fit` prints the polynomial behind each line, and a number extrapolates that many
+    //steps ahead, with big integers since long range extrapolations overflow i64
+    for arg in std::env::args().skip(1) {
+        if arg == "--fit" {
+            for (line, sequence) in sequences.iter().enumerate() {
+                let terms = sequence
+                    .coefficients()
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, c)| *c != Ratio::from_integer(0))
+                    .map(|(power, c)| {
+                        let c = if c.is_integer() {
+                            c.to_string()
+                        } else {
+                            format!("({c})")
+                        };
+                        match power {
+                            0 => c,
+                            1 => format!("{c}x"),
+                            _ => format!("{c}x^{power}"),
+                        }
+                    })
+                    .join(" + ");
+                println!(
+                    "{:>4}: degree {}: {}",
+                    line + 1,
+                    sequence.degree(),
+                    if terms.is_empty() { "0".into() } else { terms }
+                );
+            }
+            continue;
+        }
+
+        let steps: usize = arg.parse()?;
+        let total = input
+            .lines()
+            .map(|line| Ok(Sequence::<BigInt>::fit(&parse_line(line)?)?.next(steps)))
+            .sum::<anyhow::Result<BigInt>>()?;
+        println!("{steps} steps ahead: {total}");
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_example() {
+        let fit = |values: [i64; 6]| Sequence::fit(&values).unwrap();
+        let linear = fit([0, 3, 6, 9, 12, 15]);
+        let triangular = fit([1, 3, 6, 10, 15, 21]);
+        let cubic = fit([10, 13, 16, 21, 30, 45]);
+
+        assert_eq!(
+            [&linear, &triangular, &cubic].map(|s| (s.degree(), s.next(1), s.previous(1))),
+            [(1, 18, -3), (2, 28, 0), (3, 68, 5)]
+        );
+        assert_eq!(linear.next(10), 15 + 30);
+        assert_eq!(linear.previous(10), -30);
+        assert_eq!(triangular.previous(2), 0);
+        assert_eq!(triangular.previous(3), 1);
+        assert!((0..6).all(|i| cubic.at(i) == [10, 13, 16, 21, 30, 45][i as usize]));
+
+        let r = |n, d| Ratio::new(n, d);
+        assert_eq!(linear.coefficients(), [r(0, 1), r(3, 1)]);
+        assert_eq!(triangular.coefficients(), [r(1, 1), r(3, 2), r(1, 2)]);
+        assert_eq!(
+            fit([7, 7, 7, 7, 7, 7]).coefficients(),
+            [Ratio::from_integer(7)]
+        );
+    }
+
+    #[test]
+    fn test_big_extrapolation() {
+        //x^5 - 3x, well past i64 a million steps out
+        let values = (0..8)
+            .map(|x: i64| BigInt::from(x.pow(5) - 3 * x))
+            .collect_vec();
+        let sequence = Sequence::fit(&values).unwrap();
+        assert_eq!(sequence.degree(), 5);
+        let x = BigInt::from(1_000_007);
+        assert_eq!(sequence.next(1_000_000), x.pow(5) - 3 * x);
+        let x = BigInt::from(-2_000_000_000i64);
+        assert_eq!(sequence.previous(2_000_000_000), x.pow(5) - 3 * x);
+
+        let coefficients = sequence.coefficients();
+        assert_eq!(
+            coefficients,
+            [0, -3, 0, 0, 0, 1].map(|c| Ratio::from_integer(BigInt::from(c)))
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(Sequence::<i64>::fit(&[]).is_err());
+        assert!(Sequence::fit(&[5i64]).is_err());
+        //two points can't show the differences settling
+        assert!(Sequence::fit(&[1i64, 2]).is_err());
+        assert_eq!(Sequence::fit(&[4i64, 4]).unwrap().degree(), 0);
+        //powers of two never have constant differences
+        let err = Sequence::fit(&[1i64, 2, 4, 8, 16]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "not a polynomial of degree below 4: the differences never settle"
+        );
+        assert_eq!(Sequence::fit(&[1i64, 2, 4, 7, 11]).unwrap().degree(), 2);
+    }
+
+    #[test]
+    fn test_difference_triangle() {
+        assert_eq!(
+            difference_triangle(&[0i64, 3, 6, 9, 12, 15]),
+            "\
+0   3   6   9  12  15  18
+  3   3   3   3   3   3
+    0   0   0   0   0
+"
+        );
+        assert_eq!(
+            difference_triangle(&[1i64, 2, 4, 8]),
+            "\
+1   2   4   8
+  1   2   4
+    1   2
+      1
+"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use anyhow::Context;
+use aoc_2023::nonogram::{Picture, Puzzle, Solutions};
+
+//`nonogram FILE` solves a clue file, and `nonogram --clues FILE` writes the clue file for
+//a picture of `#`s and `.`s
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let arg = args.next().context("usage: nonogram [--clues] FILE")?;
+
+    if arg == "--clues" {
+        let path = args.next().context("--clues needs a picture")?;
+        let picture: Picture = std::fs::read_to_string(path)?.parse()?;
+        print!("{}", Puzzle::from_picture(&picture).to_clue_file());
+        return Ok(());
+    }
+
+    let puzzle = Puzzle::from_clue_file(&std::fs::read_to_string(&arg)?)
+        .with_context(|| format!("Invalid clue file {arg}"))?;
+    match puzzle.solve() {
+        Solutions::None => println!("no solution"),
+        Solutions::Unique(picture) => print!("{picture}"),
+        Solutions::Multiple(a, b) => print!("several solutions, including\n{a}\nand\n{b}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,4 @@
+pub mod nonogram;
+pub mod number_theory;
+pub mod spring_row;
+pub mod xorshift;
@@ -0,0 +1,406 @@
+use std::{collections::VecDeque, fmt::Display};
+
+use anyhow::{bail, Context};
+use num::{BigUint, Zero};
+
+use crate::spring_row::SpringRow;
+
+//cells are stored as in day12's rows: `#` filled, `.` empty and `?` not yet known
+const FILLED: u8 = b'#';
+const EMPTY: u8 = b'.';
+const UNKNOWN: u8 = b'?';
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Puzzle {
+    pub rows: Vec<Vec<usize>>,
+    pub columns: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Picture {
+    pub width: usize,
+    pub cells: Vec<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Solutions {
+    None,
+    Unique(Picture),
+    //two of the solutions, to show how they differ
+    Multiple(Picture, Picture),
+}
+
+//lengths of each run of filled cells
+fn clue(cells: impl IntoIterator<Item = bool>) -> Vec<usize> {
+    let mut clue = Vec::new();
+    let mut run = 0;
+    for filled in cells.into_iter().chain([false]) {
+        if filled {
+            run += 1;
+        } else if run > 0 {
+            clue.push(run);
+            run = 0;
+        }
+    }
+    clue
+}
+
+impl Picture {
+    pub fn height(&self) -> usize {
+        self.cells.len().checked_div(self.width).unwrap_or(0)
+    }
+}
+
+//parses `#` as filled and anything else as empty, one row per line
+impl std::str::FromStr for Picture {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<_> = s.lines().collect();
+        let width = lines.first().map_or(0, |line| line.len());
+        if lines.iter().any(|line| line.len() != width) {
+            bail!("rows are not all the same width");
+        }
+        Ok(Self {
+            width,
+            cells: lines
+                .iter()
+                .flat_map(|line| line.bytes().map(|c| c == FILLED))
+                .collect(),
+        })
+    }
+}
+
+impl Display for Picture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.cells.chunks(self.width.max(1)) {
+            for &filled in row {
+                write!(f, "{}", if filled { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+//one line of the grid, as either a row or a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line {
+    Row(usize),
+    Column(usize),
+}
+
+impl Puzzle {
+    pub fn from_picture(picture: &Picture) -> Self {
+        let (width, height) = (picture.width, picture.height());
+        Self {
+            rows: (0..height)
+                .map(|y| clue((0..width).map(|x| picture.cells[y * width + x])))
+                .collect(),
+            columns: (0..width)
+                .map(|x| clue((0..height).map(|y| picture.cells[y * width + x])))
+                .collect(),
+        }
+    }
+
+    //a `rows` line followed by one clue per row, then the same for `columns`. Clues are
+    //whitespace separated lengths, `0` or `-` for a line with nothing filled, and `#` starts
+    //a comment
+    pub fn from_clue_file(s: &str) -> anyhow::Result<Self> {
+        let mut seen_rows = false;
+        let mut seen_columns = false;
+        let mut section: Option<&mut Vec<Vec<usize>>> = None;
+
+        let mut row_clues = Vec::new();
+        let mut column_clues = Vec::new();
+        for (n, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            match line {
+                "" => continue,
+                "rows" => {
+                    seen_rows = true;
+                    section = Some(&mut row_clues);
+                }
+                "columns" => {
+                    seen_columns = true;
+                    section = Some(&mut column_clues);
+                }
+                "-" => section
+                    .as_mut()
+                    .with_context(|| format!("line {}: clue before `rows` or `columns`", n + 1))?
+                    .push(Vec::new()),
+                _ => {
+                    let clue = line
+                        .split(|c: char| c.is_whitespace() || c == ',')
+                        .filter(|s| !s.is_empty())
+                        .map(str::parse)
+                        .collect::<Result<Vec<usize>, _>>()
+                        .with_context(|| format!("line {}: invalid clue", n + 1))?
+                        .into_iter()
+                        .filter(|&length| length > 0)
+                        .collect();
+                    section
+                        .as_mut()
+                        .with_context(|| {
+                            format!("line {}: clue before `rows` or `columns`", n + 1)
+                        })?
+                        .push(clue);
+                }
+            }
+        }
+        if !seen_rows || !seen_columns {
+            bail!("clue files need both a `rows` and a `columns` section");
+        }
+
+        Ok(Self {
+            rows: row_clues,
+            columns: column_clues,
+        })
+    }
+
+    pub fn to_clue_file(&self) -> String {
+        let section = |clues: &[Vec<usize>]| {
+            clues
+                .iter()
+                .map(|clue| match &clue[..] {
+                    [] => "0\n".to_string(),
+                    clue => format!(
+                        "{}\n",
+                        clue.iter()
+                            .map(usize::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ),
+                })
+                .collect::<String>()
+        };
+        format!(
+            "rows\n{}columns\n{}",
+            section(&self.rows),
+            section(&self.columns)
+        )
+    }
+
+    fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    fn line_cells(&self, line: Line) -> Vec<usize> {
+        let width = self.width();
+        match line {
+            Line::Row(y) => (0..width).map(|x| y * width + x).collect(),
+            Line::Column(x) => (0..self.rows.len()).map(|y| y * width + x).collect(),
+        }
+    }
+
+    fn line_clue(&self, line: Line) -> &[usize] {
+        match line {
+            Line::Row(y) => &self.rows[y],
+            Line::Column(x) => &self.columns[x],
+        }
+    }
+
+    //fills in every cell that the line solver can deduce, revisiting lines whenever a cell
+    //across them changes, until nothing more can be learned. False if some line has no
+    //arrangement left
+    fn propagate(&self, grid: &mut [u8], mut dirty: VecDeque<Line>) -> bool {
+        let width = self.width();
+        let slot = |line: Line| match line {
+            Line::Row(y) => y,
+            Line::Column(x) => self.rows.len() + x,
+        };
+        let mut queued = vec![false; self.rows.len() + width];
+        for &line in &dirty {
+            queued[slot(line)] = true;
+        }
+
+        while let Some(line) = dirty.pop_front() {
+            queued[slot(line)] = false;
+            let cells = self.line_cells(line);
+            let pattern: String = cells.iter().map(|&i| grid[i] as char).collect();
+            //counts on a long line of short clues outgrow any fixed width integer
+            let row = SpringRow::<BigUint>::new(&pattern, self.line_clue(line));
+            if row.count().is_zero() {
+                return false;
+            }
+            for (i, c) in row.forced() {
+                let cell = cells[i];
+                grid[cell] = c as u8;
+                let across = match line {
+                    Line::Row(_) => Line::Column(cell % width),
+                    Line::Column(_) => Line::Row(cell / width),
+                };
+                if !queued[slot(across)] {
+                    queued[slot(across)] = true;
+                    dirty.push_back(across);
+                }
+            }
+        }
+        true
+    }
+
+    //finds up to `limit` solutions, guessing a cell whenever deduction gets stuck
+    fn search(
+        &self,
+        mut grid: Vec<u8>,
+        dirty: VecDeque<Line>,
+        limit: usize,
+        found: &mut Vec<Picture>,
+    ) {
+        if found.len() >= limit || !self.propagate(&mut grid, dirty) {
+            return;
+        }
+        let Some(guess) = grid.iter().position(|&c| c == UNKNOWN) else {
+            found.push(Picture {
+                width: self.width(),
+                cells: grid.iter().map(|&c| c == FILLED).collect(),
+            });
+            return;
+        };
+        let width = self.width();
+        for c in [FILLED, EMPTY] {
+            let mut grid = grid.clone();
+            grid[guess] = c;
+            let dirty = VecDeque::from([Line::Row(guess / width), Line::Column(guess % width)]);
+            self.search(grid, dirty, limit, found);
+        }
+    }
+
+    pub fn solve(&self) -> Solutions {
+        let (width, height) = (self.width(), self.rows.len());
+        let dirty = (0..height)
+            .map(Line::Row)
+            .chain((0..width).map(Line::Column))
+            .collect();
+        let mut found = Vec::new();
+        self.search(vec![UNKNOWN; width * height], dirty, 2, &mut found);
+
+        let mut found = found.into_iter();
+        match (found.next(), found.next()) {
+            (None, _) => Solutions::None,
+            (Some(a), None) => Solutions::Unique(a),
+            (Some(a), Some(b)) => Solutions::Multiple(a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn picture(s: &str) -> Picture {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_line_solving() {
+        let heart = picture(
+            "\
+.##.##.
+#######
+#######
+.#####.
+..###..
+...#...",
+        );
+        let puzzle = Puzzle::from_picture(&heart);
+        assert_eq!(puzzle.rows[0], [2, 2]);
+        assert_eq!(puzzle.columns[3], [5]);
+        assert_eq!(puzzle.solve(), Solutions::Unique(heart));
+    }
+
+    #[test]
+    fn test_clue_file() {
+        let puzzle = Puzzle::from_clue_file(
+            "# a 3x3 ring
+            rows
+            3
+            1 1
+            3
+            columns
+            3
+            1,1
+            3",
+        )
+        .unwrap();
+        assert_eq!(puzzle.rows, [vec![3], vec![1, 1], vec![3]]);
+        assert_eq!(puzzle.solve(), Solutions::Unique(picture("###\n#.#\n###")));
+        assert_eq!(
+            Puzzle::from_clue_file(&puzzle.to_clue_file()).unwrap(),
+            puzzle
+        );
+
+        let empty = Puzzle::from_clue_file("rows\n0\n-\ncolumns\n0\n0").unwrap();
+        assert_eq!(empty.rows, [vec![], vec![]]);
+        assert_eq!(empty.solve(), Solutions::Unique(picture("..\n..")));
+        assert_eq!(empty.to_clue_file(), "rows\n0\n0\ncolumns\n0\n0\n");
+
+        assert!(Puzzle::from_clue_file("1\nrows\n1\ncolumns\n1").is_err());
+        assert!(Puzzle::from_clue_file("rows\n1\n").is_err());
+        assert!(Puzzle::from_clue_file("rows\na\ncolumns\n1").is_err());
+    }
+
+    #[test]
+    fn test_no_or_several_solutions() {
+        let impossible = Puzzle {
+            rows: vec![vec![2], vec![]],
+            columns: vec![vec![], vec![]],
+        };
+        assert_eq!(impossible.solve(), Solutions::None);
+
+        //either diagonal
+        let ambiguous = Puzzle {
+            rows: vec![vec![1], vec![1]],
+            columns: vec![vec![1], vec![1]],
+        };
+        let Solutions::Multiple(a, b) = ambiguous.solve() else {
+            panic!("both diagonals fit")
+        };
+        assert_ne!(a, b);
+        assert_eq!(Puzzle::from_picture(&a), ambiguous);
+        assert_eq!(Puzzle::from_picture(&b), ambiguous);
+    }
+
+    #[test]
+    fn test_long_line() {
+        //70 single cells in a 209 wide row have more arrangements than a u128 holds
+        let cells: Vec<bool> = (0..209).map(|i| i % 3 == 0).collect();
+        let row = Picture { width: 209, cells };
+        let puzzle = Puzzle::from_picture(&row);
+        assert_eq!(puzzle.rows[0].len(), 70);
+        assert_eq!(puzzle.solve(), Solutions::Unique(row));
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        //every 3x4 picture, classified by how many pictures share its clues
+        let (width, height) = (3, 4);
+        let pictures: Vec<Picture> = (0..1u32 << (width * height))
+            .map(|bits| Picture {
+                width,
+                cells: (0..width * height).map(|i| bits >> i & 1 == 1).collect(),
+            })
+            .collect();
+        let mut sharing = std::collections::HashMap::new();
+        for p in &pictures {
+            *sharing.entry(Puzzle::from_picture(p)).or_insert(0) += 1;
+        }
+
+        for p in &pictures {
+            let puzzle = Puzzle::from_picture(p);
+            match puzzle.solve() {
+                Solutions::Unique(solution) => {
+                    assert_eq!(sharing[&puzzle], 1);
+                    assert_eq!(&solution, p);
+                }
+                Solutions::Multiple(a, b) => {
+                    assert!(sharing[&puzzle] > 1);
+                    assert_ne!(a, b);
+                    assert_eq!(Puzzle::from_picture(&a), puzzle);
+                    assert_eq!(Puzzle::from_picture(&b), puzzle);
+                }
+                Solutions::None => panic!("{p} has a solution"),
+            }
+        }
+    }
+}
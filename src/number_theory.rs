@@ -0,0 +1,143 @@
+use num::{Integer, Signed};
+
+//returns (g, x, y) such that a*x + b*y = g, where g = gcd(a, b) is non-negative
+pub fn extended_gcd<T: Integer + Signed + Clone>(a: T, b: T) -> (T, T, T) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (T::one(), T::zero());
+    let (mut old_y, mut y) = (T::zero(), T::one());
+
+    while !r.is_zero() {
+        let q = old_r.clone() / r.clone();
+        (old_r, r) = (r.clone(), old_r - q.clone() * r);
+        (old_x, x) = (x.clone(), old_x - q.clone() * x);
+        (old_y, y) = (y.clone(), old_y - q * y);
+    }
+
+    if old_r.is_negative() {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+//merges x ≡ a (mod n) and x ≡ b (mod m) into a single x ≡ c (mod lcm(n, m)),
+//or None if the two can never hold at once. Moduli need not be coprime
+pub fn crt_pair<T: Integer + Signed + Clone>((a, n): (T, T), (b, m): (T, T)) -> Option<(T, T)> {
+    let (g, u, _) = extended_gcd(n.clone(), m.clone());
+    let (k, r) = (b - a.clone()).div_rem(&g);
+    if !r.is_zero() {
+        return None;
+    }
+
+    // n*u ≡ g (mod m), so a + n*u*k ≡ a + (b - a) ≡ b (mod m). Only u*k mod m/g matters,
+    //and reducing it first keeps n*t below the lcm, so nothing overflows when the lcm fits
+    let m_g = m / g;
+    let t = (k.mod_floor(&m_g) * u.mod_floor(&m_g)).mod_floor(&m_g);
+    let lcm = n.clone() * m_g;
+    let x = (a + n * t).mod_floor(&lcm);
+    Some((x, lcm))
+}
+
+//the smallest non-negative residue and modulus satisfying every x ≡ a (mod n),
+//or None if the system has no solution
+pub fn crt<T: Integer + Signed + Clone>(
+    congruences: impl IntoIterator<Item = (T, T)>,
+) -> Option<(T, T)> {
+    congruences
+        .into_iter()
+        .try_fold((T::zero(), T::one()), crt_pair)
+}
+
+pub fn lcm_all<T: Integer + Clone>(xs: impl IntoIterator<Item = T>) -> T {
+    xs.into_iter().fold(T::one(), |acc, x| acc.lcm(&x))
+}
+
+#[cfg(test)]
+mod test {
+    use num::BigInt;
+
+    use super::*;
+
+    #[test]
+    fn test_extended_gcd() {
+        for (a, b) in [
+            (240i64, 46),
+            (46, 240),
+            (17, 5),
+            (0, 7),
+            (7, 0),
+            (-12, 18),
+            (12, -18),
+        ] {
+            let (g, x, y) = extended_gcd(a, b);
+            assert_eq!(g, a.gcd(&b), "gcd({a}, {b})");
+            assert_eq!(a * x + b * y, g, "bézout({a}, {b})");
+        }
+        assert_eq!(extended_gcd(0i128, 0), (0, 1, 0));
+
+        let a: BigInt = "1234567890123456789012345678901234567890".parse().unwrap();
+        let b: BigInt = "987654321098765432109876543210".parse().unwrap();
+        let (g, x, y) = extended_gcd(a.clone(), b.clone());
+        assert_eq!(g, a.gcd(&b));
+        assert_eq!(a * x + b * y, g);
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt([(2i64, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        //moduli sharing a factor, with compatible residues
+        assert_eq!(crt([(3i64, 4), (5, 6)]), Some((11, 12)));
+        //and incompatible ones
+        assert_eq!(crt([(1i64, 4), (2, 6)]), None);
+        assert_eq!(crt(Vec::<(i128, i128)>::new()), Some((0, 1)));
+        assert_eq!(crt([(-1i128, 10)]), Some((9, 10)));
+
+        let big = |x: i64| BigInt::from(x);
+        assert_eq!(
+            crt([(big(0), big(21)), (big(3), big(6))]),
+            Some((big(21), big(42)))
+        );
+
+        //moduli near 1e9, whose lcm fits in an i64 though n*u*k wouldn't, agree with BigInt
+        for ((a, n), (b, m)) in [
+            ((0i64, 1_000_000_007), (998_244_352, 998_244_353)),
+            ((0, 2_147_483_647), (2_147_483_628, 2_147_483_629)),
+            ((123_456_789, 1_000_000_009), (987_654_321, 999_999_937)),
+            ((5, 999_999_000), (1_005, 1_000_000_000)),
+        ] {
+            let merged = crt_pair((a, n), (b, m)).map(|(x, lcm)| (big(x), big(lcm)));
+            assert_eq!(merged, crt_pair((big(a), big(n)), (big(b), big(m))));
+            let (x, lcm) = merged.unwrap();
+            assert_eq!(
+                (&x % big(n), &x % big(m), lcm),
+                (big(a), big(b), big(n).lcm(&big(m)))
+            );
+        }
+
+        //brute force every small system
+        for n in 1i64..12 {
+            for m in 1i64..12 {
+                for a in 0..n {
+                    for b in 0..m {
+                        let expected = (0..n * m).find(|x| x % n == a && x % m == b);
+                        let merged = crt_pair((a, n), (b, m));
+                        assert_eq!(merged.map(|(x, _)| x), expected, "{a} mod {n}, {b} mod {m}");
+                        if let Some((_, lcm)) = merged {
+                            assert_eq!(lcm, n.lcm(&m));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lcm_all() {
+        assert_eq!(lcm_all([4i64, 6, 10]), 60);
+        assert_eq!(lcm_all(Vec::<i64>::new()), 1);
+        assert_eq!(
+            lcm_all([3931, 3907, 4057, 3967].map(BigInt::from)),
+            BigInt::from(247_180_190_849_623u64)
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use num::Num;
+
+//a row of springs, `#` damaged, `.` operational and `?` unknown, with the sizes of each
+//contiguous group of damaged springs. Counts are `N`s, which can be big integers for rows
+//with more arrangements than fit in a u64
+pub struct SpringRow<N = u64> {
+    cells: Vec<u8>,
+    groups: Vec<usize>,
+    //number of cells from each index up to the next known operational spring
+    runs: Vec<usize>,
+    //ways[p * (groups.len() + 1) + g] is the number of arrangements of groups[g..] into
+    //cells[p..], where p is somewhere a group is free to start
+    ways: Vec<N>,
+}
+
+impl<N: Num + Clone + PartialOrd> SpringRow<N> {
+    pub fn new(pattern: &str, groups: &[usize]) -> Self {
+        let cells = pattern.as_bytes().to_vec();
+        let n = cells.len();
+
+        let mut runs = vec![0; n + 1];
+        for p in (0..n).rev() {
+            runs[p] = if cells[p] == b'.' { 0 } else { runs[p + 1] + 1 };
+        }
+
+        let mut row = Self {
+            cells,
+            groups: groups.to_vec(),
+            runs,
+            ways: vec![N::zero(); (n + 1) * (groups.len() + 1)],
+        };
+
+        //filled from the end, since each state only leads further along the row
+        *row.ways_mut(n, groups.len()) = N::one();
+        for p in (0..n).rev() {
+            for g in 0..=groups.len() {
+                let mut ways = N::zero();
+                if row.cells[p] != b'#' {
+                    ways = ways + row.ways(p + 1, g).clone();
+                }
+                if let Some(next) = row.place(p, g) {
+                    ways = ways + row.ways(next, g + 1).clone();
+                }
+                *row.ways_mut(p, g) = ways;
+            }
+        }
+        row
+    }
+
+    fn ways(&self, p: usize, g: usize) -> &N {
+        &self.ways[p * (self.groups.len() + 1) + g]
+    }
+
+    fn ways_mut(&mut self, p: usize, g: usize) -> &mut N {
+        &mut self.ways[p * (self.groups.len() + 1) + g]
+    }
+
+    //where the next group could start after putting group g at p, if it fits there
+    fn place(&self, p: usize, g: usize) -> Option<usize> {
+        let end = p + self.groups.get(g)?;
+        (self.runs[p] >= end - p && self.cells.get(end) != Some(&b'#'))
+            .then_some((end + 1).min(self.cells.len()))
+    }
+
+    pub fn count(&self) -> N {
+        self.ways(0, 0).clone()
+    }
+
+    //the k-th arrangement in lexicographic order, where `#` comes before `.`
+    pub fn arrangement(&self, mut k: N) -> Option<String> {
+        if k >= *self.ways(0, 0) {
+            return None;
+        }
+        let n = self.cells.len();
+        let mut out = String::with_capacity(n);
+        let (mut p, mut g) = (0, 0);
+        while p < n {
+            if let Some(next) = self.place(p, g) {
+                let ways = self.ways(next, g + 1);
+                if k < *ways {
+                    out.extend(std::iter::repeat_n('#', self.groups[g]));
+                    if next > p + self.groups[g] {
+                        out.push('.');
+                    }
+                    (p, g) = (next, g + 1);
+                    continue;
+                }
+                k = k - ways.clone();
+            }
+            out.push('.');
+            p += 1;
+        }
+        Some(out)
+    }
+
+    //every arrangement in lexicographic order, each found directly, so none are wasted
+    pub fn arrangements(&self) -> impl Iterator<Item = String> + '_ {
+        std::iter::successors(Some(N::zero()), |k| Some(k.clone() + N::one()))
+            .map_while(|k| self.arrangement(k))
+    }
+
+    //the number of arrangements in which each cell is damaged
+    pub fn damaged_counts(&self) -> Vec<N> {
+        let n = self.cells.len();
+        let width = self.groups.len() + 1;
+
+        //reaching[p * width + g] is the number of ways to fill cells[..p] with groups[..g]
+        let mut reaching = vec![N::zero(); (n + 1) * width];
+        reaching[0] = N::one();
+        //arrangements whose groups start and end at each cell, summed up afterwards
+        let mut starting = vec![N::zero(); n + 1];
+        let mut ending = vec![N::zero(); n + 1];
+        for p in 0..n {
+            for g in 0..width {
+                let reached = reaching[p * width + g].clone();
+                if reached.is_zero() {
+                    continue;
+                }
+                if self.cells[p] != b'#' {
+                    let i = (p + 1) * width + g;
+                    reaching[i] = reaching[i].clone() + reached.clone();
+                }
+                if let Some(next) = self.place(p, g) {
+                    let i = next * width + g + 1;
+                    reaching[i] = reaching[i].clone() + reached.clone();
+                    let through = reached * self.ways(next, g + 1).clone();
+                    let end = p + self.groups[g];
+                    starting[p] = starting[p].clone() + through.clone();
+                    ending[end] = ending[end].clone() + through;
+                }
+            }
+        }
+
+        //groups ending here were counted earlier, so this never drops below zero
+        let mut damaged = N::zero();
+        (0..n)
+            .map(|i| {
+                damaged = damaged.clone() - ending[i].clone() + starting[i].clone();
+                damaged.clone()
+            })
+            .collect()
+    }
+
+    //the unknown cells which are the same in every arrangement, as they must be
+    pub fn forced(&self) -> Vec<(usize, char)> {
+        let total = self.count();
+        if total.is_zero() {
+            return Vec::new();
+        }
+        self.damaged_counts()
+            .into_iter()
+            .enumerate()
+            .filter(|&(i, _)| self.cells[i] == b'?')
+            .filter_map(|(i, damaged)| {
+                if damaged.is_zero() {
+                    Some((i, '.'))
+                } else if damaged == total {
+                    Some((i, '#'))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+    use num::BigUint;
+
+    use super::*;
+
+    //every way of filling in the unknowns which fits the groups, sorted
+    fn brute_force(pattern: &str, groups: &[usize]) -> Vec<String> {
+        pattern
+            .chars()
+            .map(|c| match c {
+                '?' => vec!['#', '.'],
+                c => vec![c],
+            })
+            .multi_cartesian_product()
+            .map(String::from_iter)
+            .filter(|s| {
+                s.split('.')
+                    .filter(|g| !g.is_empty())
+                    .map(str::len)
+                    .eq(groups.iter().copied())
+            })
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn test_arrangements() {
+        for (pattern, groups) in [
+            ("???.###", vec![1, 1, 3]),
+            (".??..??...?##.", vec![1, 1, 3]),
+            ("?#?#?#?#?#?#?#?", vec![1, 3, 1, 6]),
+            ("????.#...#...", vec![4, 1, 1]),
+            ("????.######..#####.", vec![1, 6, 5]),
+            ("?###????????", vec![3, 2, 1]),
+            ("??????", vec![]),
+            ("#??", vec![]),
+        ] {
+            let row = SpringRow::<u64>::new(pattern, &groups);
+            let expected = brute_force(pattern, &groups);
+            assert_eq!(row.count(), expected.len() as u64, "{pattern}");
+            assert_eq!(row.arrangements().collect_vec(), expected, "{pattern}");
+            assert_eq!(row.arrangement(row.count()), None);
+        }
+        let row = SpringRow::<u64>::new("?###????????", &[3, 2, 1]);
+        assert_eq!(row.arrangement(0).unwrap(), ".###.##.#...");
+        assert_eq!(row.arrangement(9).unwrap(), ".###....##.#");
+    }
+
+    #[test]
+    fn test_forced() {
+        assert_eq!(
+            SpringRow::<u64>::new("???.###", &[1, 1, 3]).forced(),
+            [(0, '#'), (1, '.'), (2, '#')]
+        );
+        assert_eq!(SpringRow::<u64>::new("?#?", &[2]).forced(), []);
+        assert_eq!(SpringRow::<u64>::new("??#??", &[3]).forced(), []);
+        assert_eq!(SpringRow::<u64>::new("???", &[2]).forced(), [(1, '#')]);
+        assert_eq!(
+            SpringRow::<u64>::new("?????#", &[1, 2]).forced(),
+            [(3, '.'), (4, '#')]
+        );
+        //nothing is forced when there's no arrangement at all
+        assert_eq!(SpringRow::<u64>::new("?.?", &[2]).forced(), []);
+
+        for (pattern, groups) in [("?###????????", vec![3, 2, 1]), ("??.??#??", vec![1, 3])] {
+            let row = SpringRow::<u64>::new(pattern, &groups);
+            let expected = brute_force(pattern, &groups);
+            let damaged = (0..pattern.len())
+                .map(|i| expected.iter().filter(|s| s.as_bytes()[i] == b'#').count() as u64)
+                .collect_vec();
+            assert_eq!(row.damaged_counts(), damaged);
+        }
+    }
+
+    #[test]
+    fn test_big_counts() {
+        //n groups of one in 3n - 1 unknowns leave n + 1 gaps to spread n - 1 spare cells
+        //over, which is C(2n, n) ways, too many for a u128 once n is 70
+        let n = 70;
+        let pattern = "?".repeat(3 * n - 1);
+        let row = SpringRow::<BigUint>::new(&pattern, &vec![1; n]);
+        let binomial = (1..=n).fold(BigUint::from(1u8), |c, k| c * (n + k) / k);
+        assert_eq!(row.count(), binomial);
+        assert!(row.count() > BigUint::from(u128::MAX));
+
+        let small = SpringRow::<u128>::new(&"?".repeat(3 * 30 - 1), &[1; 30]);
+        assert_eq!(
+            BigUint::from(small.count()),
+            (1..=30u32).fold(BigUint::from(1u8), |c, k| c * (30 + k) / k)
+        );
+        assert_eq!(
+            row.arrangement(BigUint::from(0u8)).unwrap(),
+            "#.".repeat(n) + &".".repeat(n - 1)
+        );
+    }
+}
@@ -0,0 +1,37 @@
+//a small deterministic generator for synthetic test and benchmark inputs
+#[derive(Debug, Clone)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    //a zero state would only ever produce zeros
+    pub fn new(seed: u64) -> Self {
+        assert_ne!(seed, 0, "xorshift needs a non-zero seed");
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    //slightly biased towards small values, which doesn't matter for tests
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_xorshift() {
+        let mut a = XorShift::new(1);
+        assert_eq!(a.next_u64(), 1082269761);
+        let mut b = a.clone();
+        assert_eq!(a.next_u64(), b.next_u64());
+        assert!((0..1000).all(|_| a.below(7) < 7));
+    }
+}